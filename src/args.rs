@@ -1,4 +1,6 @@
 use crate::backends::BuildProfile;
+use crate::package::features::FeatureRequest;
+use crate::package::management::ResolutionOptions;
 use clap::{Args, Parser, Subcommand};
 use serde_derive::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -33,6 +35,19 @@ pub enum BuildSystem {
     Cargo,
     Npm,
     Pnpm,
+    Pip,
+    West,
+    Pico,
+}
+
+/// Document formats of the software bill of materials
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[value(name = "cyclonedx")]
+    CycloneDx,
+    /// SPDX 2.3 JSON
+    Spdx,
 }
 
 #[derive(Args, Debug)]
@@ -72,6 +87,54 @@ pub struct BuildArgs {
     /// Number of threads to use for parallel builds. Zero means it will be determined automatically.
     #[arg(short, long, default_value_t = 0)]
     pub threads: usize,
+
+    /// CMake toolchain file the C, C++ and uC apps are cross-compiled with, replaces the file of their [app.toolchain]
+    #[arg(long)]
+    pub toolchain: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub dependencies: DependencyArgs,
+}
+
+/// Flags controlling how dependencies are resolved
+#[derive(Args, Debug, Clone, Default)]
+pub struct DependencyArgs {
+    /// Fail instead of updating Lingo.lock if dependency resolution would change it
+    #[arg(long)]
+    pub locked: bool,
+
+    /// Only use already fetched dependencies and never access the network
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Equivalent to passing both --locked and --offline
+    #[arg(long)]
+    pub frozen: bool,
+
+    /// Features of this package to enable in addition to the default features
+    #[arg(short = 'F', long, value_delimiter = ',')]
+    pub features: Vec<String>,
+
+    /// Don't enable the default features of this package
+    #[arg(long)]
+    pub no_default_features: bool,
+}
+
+impl DependencyArgs {
+    pub fn resolution_options(&self) -> ResolutionOptions {
+        ResolutionOptions {
+            locked: self.locked || self.frozen,
+            offline: self.offline || self.frozen,
+        }
+    }
+
+    /// features of this package that were selected on the command line
+    pub fn feature_request(&self) -> FeatureRequest {
+        FeatureRequest {
+            features: self.features.iter().cloned().collect(),
+            default: !self.no_default_features,
+        }
+    }
 }
 
 impl BuildArgs {
@@ -84,6 +147,24 @@ impl BuildArgs {
     }
 }
 
+#[derive(Args, Debug)]
+pub struct SbomArgs {
+    /// Format of the document
+    #[arg(long, value_enum, default_value_t = SbomFormat::CycloneDx)]
+    pub format: SbomFormat,
+
+    /// Writes the document to the file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Tell lingo where the lfc toolchain can be found
+    #[arg(long)]
+    pub lfc: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub dependencies: DependencyArgs,
+}
+
 #[derive(Args, Debug)]
 pub struct InitArgs {
     #[arg(value_enum, short, long)]
@@ -106,6 +187,20 @@ impl InitArgs {
     }
 }
 
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Fetches missing or modified packages again from the source recorded in Lingo.lock
+    #[arg(long)]
+    pub repair: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct HashArgs {
+    /// Directory to compute the checksum of
+    #[arg(default_value = ".")]
+    pub path: PathBuf,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// initializing a lingua-franca project
@@ -122,6 +217,21 @@ pub enum Command {
 
     /// removes build artifacts
     Clean,
+
+    /// checks the fetched dependencies against the checksums in Lingo.lock
+    Verify(VerifyArgs),
+
+    /// prints the checksum of a directory as it is recorded in Lingo.lock
+    Hash(HashArgs),
+
+    /// lists the locked, the newest compatible and the newest available version of the dependencies
+    Outdated,
+
+    /// lists the license and source of every dependency
+    Licenses(DependencyArgs),
+
+    /// exports a software bill of materials of the project and its dependencies
+    Sbom(SbomArgs),
 }
 
 #[derive(Parser)]
@@ -142,3 +252,43 @@ pub struct CommandLineArgs {
     #[arg(short, long)]
     pub verbose: bool,
 }
+
+#[test]
+fn test_dependency_args() {
+    <CommandLineArgs as clap::CommandFactory>::command().debug_assert();
+
+    let args = CommandLineArgs::parse_from(["lingo", "build", "--frozen"]);
+    let Command::Build(build) = args.command else {
+        panic!("expected the build command");
+    };
+    let options = build.dependencies.resolution_options();
+    assert!(options.locked && options.offline);
+
+    let args = CommandLineArgs::parse_from(["lingo", "licenses", "--offline"]);
+    let Command::Licenses(dependencies) = args.command else {
+        panic!("expected the licenses command");
+    };
+    let options = dependencies.resolution_options();
+    assert!(!options.locked && options.offline);
+    assert_eq!(
+        dependencies.feature_request(),
+        FeatureRequest::default_features()
+    );
+
+    let args = CommandLineArgs::parse_from([
+        "lingo",
+        "build",
+        "--features",
+        "tracing,mqtt",
+        "--no-default-features",
+    ]);
+    let Command::Build(build) = args.command else {
+        panic!("expected the build command");
+    };
+    let request = build.dependencies.feature_request();
+    assert!(!request.default);
+    assert_eq!(
+        Vec::from_iter(request.features),
+        ["mqtt".to_string(), "tracing".to_string()]
+    );
+}
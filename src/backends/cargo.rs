@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::backends::{
+    BatchBackend, BatchBuildResults, BuildCommandOptions, BuildProfile, BuildResult, CommandSpec,
+};
+use crate::package::App;
+use crate::util::errors::LingoError;
+use crate::util::execute_command_to_build_result;
+
+pub struct Cargo;
+
+/// directory of the crate lfc generated for the app
+fn crate_dir(app: &App) -> PathBuf {
+    app.src_gen_dir().join(&app.main_reactor_name)
+}
+
+/// the shared target directory of the rust apps with this output directory
+fn target_dir(output_root: &Path) -> PathBuf {
+    output_root.join("target")
+}
+
+/// name of the binary the generated crate produces, the first `[[bin]]` or the package name
+fn binary_name(app: &App) -> anyhow::Result<String> {
+    let manifest: toml::Table = fs::read_to_string(crate_dir(app).join("Cargo.toml"))?.parse()?;
+    manifest
+        .get("bin")
+        .and_then(|bins| bins.as_array())
+        .and_then(|bins| bins.first())
+        .and_then(|bin| bin.get("name"))
+        .or_else(|| {
+            manifest
+                .get("package")
+                .and_then(|package| package.get("name"))
+        })
+        .and_then(|name| name.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("the crate of {} doesn't declare a binary name", app.name))
+}
+
+/// Writes a cargo workspace containing the generated crates of all apps, so they are built with
+/// one cargo invocation and share the compiled dependencies.
+fn write_workspace(apps: &[&App]) -> BuildResult {
+    let src_gen = apps[0].src_gen_dir();
+    let members = apps
+        .iter()
+        .map(|app| format!("    \"{}\",\n", app.main_reactor_name))
+        .collect::<String>();
+    fs::write(
+        src_gen.join("Cargo.toml"),
+        format!("# generated by lingo\n[workspace]\nresolver = \"2\"\nmembers = [\n{members}]\n"),
+    )?;
+    Ok(())
+}
+
+fn do_cargo_build(results: &mut BatchBuildResults, options: &BuildCommandOptions) {
+    results.keep_going(options.keep_going);
+    super::lfc::LFC::do_parallel_lfc_codegen(options, results, false);
+    if !options.compile_target_code {
+        return;
+    }
+    results
+        .gather(|apps| {
+            // apps with a toolchain of their own have a separate output directory
+            let mut workspaces = BTreeMap::<&Path, Vec<&App>>::new();
+            for &app in apps {
+                workspaces.entry(&app.output_root).or_default().push(app);
+            }
+            for (output_root, apps) in workspaces {
+                write_workspace(&apps)?;
+
+                let mut cargo = Command::new("cargo");
+                cargo.arg("build");
+                cargo.arg("--manifest-path");
+                cargo.arg(apps[0].src_gen_dir().join("Cargo.toml"));
+                cargo.arg("--workspace");
+                cargo.arg("--target-dir");
+                cargo.arg(target_dir(output_root));
+                if options.profile == BuildProfile::Release {
+                    cargo.arg("--release");
+                }
+                if options.max_threads != 0 {
+                    cargo.arg("-j");
+                    cargo.arg(options.max_threads.to_string());
+                }
+                execute_command_to_build_result(cargo)?;
+            }
+            Ok(())
+        })
+        .map(|app| {
+            let profile = match options.profile {
+                BuildProfile::Release => "release",
+                BuildProfile::Debug => "debug",
+            };
+            // the binary name may contain dots, so the extension is appended instead of replacing
+            let mut file_name = binary_name(app)?;
+            if !std::env::consts::EXE_EXTENSION.is_empty() {
+                file_name = format!("{file_name}.{}", std::env::consts::EXE_EXTENSION);
+            }
+            let binary = target_dir(&app.output_root).join(profile).join(file_name);
+            if !binary.exists() {
+                return Err(Box::new(LingoError::MissingBinary(binary)));
+            }
+
+            fs::create_dir_all(app.output_root.join("bin"))?;
+            fs::copy(binary, app.executable_path())?;
+            Ok(())
+        });
+}
+
+impl BatchBackend for Cargo {
+    fn execute_command(&mut self, command: &CommandSpec, results: &mut BatchBuildResults) {
+        match command {
+            CommandSpec::Build(options) => do_cargo_build(results, options),
+            CommandSpec::Clean => {
+                results.par_map(|app| {
+                    crate::util::default_build_clean(&app.output_root)?;
+                    crate::util::delete_subdirs(&app.output_root, &["target"])?;
+                    Ok(())
+                });
+            }
+            _ => todo!(),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cargo_workspace_build() {
+    let package = tempfile::tempdir().unwrap();
+    let apps: Vec<App> = [
+        ("robot", "Robot", ""),
+        ("drone", "Drone", ""),
+        ("rover", "Rover", "\n[toolchain]\ntriple = \"aarch64-unknown-linux-gnu\""),
+    ]
+    .into_iter()
+    .map(|(name, main, toolchain)| {
+        let file: crate::package::AppFile = toml::from_str(&format!(
+            "name = \"{name}\"\ntarget = \"Rust\"\nmain = \"src/{main}.lf\"\nproperties = {{}}{toolchain}"
+        ))
+        .unwrap();
+        file.convert(name, package.path())
+    })
+    .collect();
+    assert_ne!(apps[0].output_root, apps[2].output_root);
+    for (app, manifest) in apps.iter().zip([
+        "[package]\nname = \"robot-crate\"\n\n[[bin]]\nname = \"robot-bin\"\n",
+        "[package]\nname = \"drone\"\n",
+        "[package]\nname = \"rover.app\"\n",
+    ]) {
+        fs::create_dir_all(crate_dir(app)).unwrap();
+        fs::write(crate_dir(app).join("Cargo.toml"), manifest).unwrap();
+    }
+    assert_eq!(binary_name(&apps[0]).unwrap(), "robot-bin");
+    assert_eq!(binary_name(&apps[1]).unwrap(), "drone");
+
+    // the stand-in records its arguments and produces the binaries in the given target folder
+    let cargo = "echo \"$@\" >> \"$0.log\"\n\
+                 while [ $# -gt 0 ]; do [ \"$1\" = --target-dir ] && target=\"$2\"; shift; done\n\
+                 mkdir -p \"$target/debug\" && cd \"$target/debug\" && touch robot-bin drone rover.app";
+    let log = super::with_fake_tools(&[("lfc", ""), ("cargo", cargo)], |bin| {
+        let mut results = BatchBuildResults::for_apps(&apps.iter().collect::<Vec<_>>());
+        Cargo.execute_command(
+            &CommandSpec::Build(super::test_build_options(bin)),
+            &mut results,
+        );
+        for (app, result) in &results.results {
+            assert!(result.is_ok(), "{}: {:?}", app.name, result);
+        }
+        fs::read_to_string(bin.join("cargo.log")).unwrap()
+    });
+
+    // one workspace and target directory per output directory
+    let mut log: Vec<&str> = log.lines().collect();
+    log.sort();
+    let mut expected: Vec<String> = [&apps[0], &apps[2]]
+        .into_iter()
+        .map(|app| {
+            format!(
+                "build --manifest-path {} --workspace --target-dir {}",
+                app.src_gen_dir().join("Cargo.toml").display(),
+                target_dir(&app.output_root).display()
+            )
+        })
+        .collect();
+    expected.sort();
+    assert_eq!(log, expected);
+    let workspace = fs::read_to_string(apps[0].src_gen_dir().join("Cargo.toml")).unwrap();
+    assert!(workspace.contains("members = [\n    \"Robot\",\n    \"Drone\",\n]"));
+    let workspace = fs::read_to_string(apps[2].src_gen_dir().join("Cargo.toml")).unwrap();
+    assert!(workspace.contains("members = [\n    \"Rover\",\n]"));
+    for app in &apps {
+        assert!(app.executable_path().exists(), "{}", app.name);
+    }
+}
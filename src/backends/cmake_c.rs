@@ -21,9 +21,7 @@ fn gen_cmake_files(app: &App, options: &BuildCommandOptions) -> BuildResult {
     let cmake_file = app_build_folder.clone().join("CMakeLists.txt");
 
     // create potential files that come from the target properties
-    app.properties
-        .write_artifacts(&app_build_folder)
-        .expect("cannot write artifacts");
+    app.properties.write_artifacts(&app_build_folder)?;
 
     // read file and append cmake include to generated cmake file
     let mut content = fs::read_to_string(&cmake_file)?;
@@ -31,12 +29,9 @@ fn gen_cmake_files(app: &App, options: &BuildCommandOptions) -> BuildResult {
     content += include_statement;
 
     // overwrite cmake file
-    let mut f = fs::OpenOptions::new()
-        .write(true)
-        .open(&cmake_file)
-        .expect("cannot open file");
-    f.write_all(content.as_ref()).expect("cannot write file");
-    f.flush().expect("cannot flush");
+    let mut f = fs::OpenOptions::new().write(true).open(&cmake_file)?;
+    f.write_all(content.as_ref())?;
+    f.flush()?;
 
     // cmake args
     let mut cmake = Command::new("cmake");
@@ -56,7 +51,9 @@ fn gen_cmake_files(app: &App, options: &BuildCommandOptions) -> BuildResult {
     cmake.arg("-DCMAKE_INSTALL_BINDIR=bin");
     cmake.arg(format!(
         "-DLF_SOURCE_DIRECTORY=\"{}\"",
-        app.src_dir_path().unwrap().display()
+        app.src_dir_path()
+            .ok_or(LingoError::InvalidMainReactor)?
+            .display()
     ));
 
     cmake.arg(format!(
@@ -80,6 +77,7 @@ fn gen_cmake_files(app: &App, options: &BuildCommandOptions) -> BuildResult {
         }
     ));
 
+    super::apply_toolchain(&mut cmake, app);
     cmake.arg(&app_build_folder);
     cmake.arg(format!("-B {}", app_build_folder.display()));
     cmake.current_dir(&build_dir);
@@ -125,6 +123,7 @@ fn do_cmake_build(results: &mut BatchBuildResults, options: &BuildCommandOptions
                 .src_gen_dir()
                 .join(&app.main_reactor_name)
                 .join(&app.main_reactor_name);
+            fs::create_dir_all(app.output_root.join("bin"))?;
             fs::rename(bin_source, app.executable_path())?;
             Ok(())
         });
@@ -144,3 +143,23 @@ impl BatchBackend for CmakeC {
         }
     }
 }
+
+#[cfg(unix)]
+#[test]
+fn test_missing_library_source() {
+    let package = tempfile::tempdir().unwrap();
+    let file: crate::package::AppFile =
+        toml::from_str("name = \"robot\"\ntarget = \"C\"\nmain = \"src/Main.lf\"\nproperties = {}")
+            .unwrap();
+    let mut app = file.convert("robot", package.path());
+    app.properties
+        .sources
+        .push(package.path().join("build/lfc_include/mqtt/c/mqtt.c"));
+    let app_build_folder = app.src_gen_dir().join(&app.main_reactor_name);
+    fs::create_dir_all(&app_build_folder).unwrap();
+    fs::write(app_build_folder.join("CMakeLists.txt"), "project(Main)\n").unwrap();
+
+    // the build of the app fails instead of lingo
+    let error = gen_cmake_files(&app, &super::test_build_options(package.path())).unwrap_err();
+    assert!(error.to_string().contains("cannot find library source"));
+}
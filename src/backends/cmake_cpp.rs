@@ -63,6 +63,7 @@ fn gen_cmake_files(app: &App, options: &BuildCommandOptions) -> BuildResult {
             .expect("not a valid main reactor path")
             .display()
     ));
+    super::apply_toolchain(&mut cmake, app);
     cmake.arg(src_gen_dir);
     cmake.arg(format!("-B {}", build_dir.display()));
     cmake.current_dir(&build_dir);
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::args::Platform;
+use crate::backends::{
+    BatchBackend, BatchBuildResults, BuildCommandOptions, BuildProfile, BuildResult, CommandSpec,
+};
+use crate::package::App;
+use crate::util::errors::LingoError;
+use crate::util::execute_command_to_build_result;
+
+pub struct CmakeUc;
+
+/// environment variable pointing to a checkout of the reactor-uc runtime
+const REACTOR_UC_PATH: &str = "REACTOR_UC_PATH";
+
+/// cmake build directory of all the apps that share the output directory
+fn build_dir(output_root: &Path) -> PathBuf {
+    output_root.join("build")
+}
+
+/// directory of the program lfc generated for the app
+fn app_dir(app: &App) -> PathBuf {
+    app.src_gen_dir().join(&app.main_reactor_name)
+}
+
+fn prepare_app(app: &App) -> BuildResult {
+    if app.platform != Platform::Native {
+        return Err(Box::new(LingoError::UnsupportedPlatform(
+            format!("{:?}", app.platform),
+            format!("{:?}", app.target),
+        )));
+    }
+
+    // location of the cmake file
+    let app_build_folder = app_dir(app);
+    let cmake_file = app_build_folder.join("CMakeLists.txt");
+
+    // create potential files that come from the target properties
+    app.properties.write_artifacts(&app_build_folder)?;
+
+    // read file and append cmake include to generated cmake file
+    let mut content = fs::read_to_string(&cmake_file)?;
+    content += "\ninclude(./aggregated_cmake_include.cmake)";
+
+    // overwrite cmake file
+    let mut f = fs::OpenOptions::new().write(true).open(&cmake_file)?;
+    f.write_all(content.as_ref())?;
+    f.flush()?;
+    Ok(())
+}
+
+/// Writes a cmake project containing all the apps. The runtime is added once and shared by
+/// the apps, the generated projects would otherwise each add it again.
+fn write_project(src_gen: &Path, apps: &[&App]) -> BuildResult {
+    let mut content = String::from(
+        "# generated by lingo\n\
+         cmake_minimum_required(VERSION 3.20)\n\
+         project(LingoApps LANGUAGES C)\n\
+         get_filename_component(LINGO_REACTOR_UC \"${REACTOR_UC_PATH}\" REALPATH)\n\
+         add_subdirectory(\"${LINGO_REACTOR_UC}\" reactor-uc)\n\
+         function(add_subdirectory source)\n\
+         \x20 get_filename_component(absolute \"${source}\" REALPATH BASE_DIR \"${CMAKE_CURRENT_SOURCE_DIR}\")\n\
+         \x20 if(NOT \"${absolute}\" STREQUAL \"${LINGO_REACTOR_UC}\")\n\
+         \x20   _add_subdirectory(${ARGV})\n\
+         \x20 endif()\n\
+         endfunction()\n",
+    );
+    for app in apps {
+        content += &format!("add_subdirectory({})\n", app.main_reactor_name);
+    }
+    fs::write(src_gen.join("CMakeLists.txt"), content)?;
+    Ok(())
+}
+
+/// Configures and builds the apps as one cmake project per output directory, apps with a
+/// toolchain of their own have a separate output directory. Apps with different toolchains
+/// may not share one.
+fn build_apps(apps: &[&App], options: &BuildCommandOptions) -> BuildResult {
+    let runtime = super::runtime_path(REACTOR_UC_PATH, "CMakeLists.txt")?;
+
+    let mut projects = BTreeMap::<&Path, Vec<&App>>::new();
+    for &app in apps {
+        projects.entry(&app.output_root).or_default().push(app);
+    }
+    // the apps of a project are configured with a single set of compilers
+    for apps in projects.values() {
+        if let Some(other) = apps.iter().find(|app| app.toolchain != apps[0].toolchain) {
+            return Err(anyhow::anyhow!(
+                "the apps {} and {} have different toolchains but share the output directory {}, give the toolchains a triple or file",
+                apps[0].name,
+                other.name,
+                apps[0].output_root.display()
+            )
+            .into());
+        }
+    }
+    for (output_root, apps) in projects {
+        let src_gen = apps[0].src_gen_dir();
+        write_project(&src_gen, &apps)?;
+
+        let mut cmake = Command::new("cmake");
+        cmake.arg(format!(
+            "-DCMAKE_BUILD_TYPE={}",
+            if options.profile == BuildProfile::Release {
+                "RELEASE"
+            } else {
+                "DEBUG"
+            }
+        ));
+        cmake.arg("-DPLATFORM=POSIX");
+        cmake.arg(format!("-D{REACTOR_UC_PATH}={}", runtime.display()));
+        cmake.env(REACTOR_UC_PATH, &runtime);
+        super::apply_toolchain(&mut cmake, apps[0]);
+        cmake.arg("-S").arg(&src_gen);
+        cmake.arg("-B").arg(build_dir(output_root));
+        execute_command_to_build_result(cmake)?;
+
+        let mut cmake = Command::new("cmake");
+        cmake.arg("--build").arg(build_dir(output_root));
+        for app in &apps {
+            cmake.arg("--target");
+            cmake.arg(&app.main_reactor_name);
+        }
+        if options.max_threads != 0 {
+            cmake.arg("--parallel");
+            cmake.arg(options.max_threads.to_string());
+        }
+        execute_command_to_build_result(cmake)?;
+    }
+    Ok(())
+}
+
+fn do_cmake_build(results: &mut BatchBuildResults, options: &BuildCommandOptions) {
+    results.keep_going(options.keep_going);
+    super::lfc::LFC::do_parallel_lfc_codegen(options, results, false);
+    if !options.compile_target_code {
+        return;
+    }
+    results
+        // generate all CMake files ahead of time
+        .map(prepare_app)
+        // all apps are compiled together against a single build of the runtime
+        .gather(|apps| build_apps(apps, options))
+        .map(|app| {
+            let bin_source = build_dir(&app.output_root)
+                .join(&app.main_reactor_name)
+                .join(&app.main_reactor_name);
+            if !bin_source.exists() {
+                return Err(Box::new(LingoError::MissingBinary(bin_source)));
+            }
+            fs::create_dir_all(app.output_root.join("bin"))?;
+            fs::rename(bin_source, app.executable_path())?;
+            Ok(())
+        });
+}
+
+impl BatchBackend for CmakeUc {
+    fn execute_command(&mut self, command: &CommandSpec, results: &mut BatchBuildResults) {
+        match command {
+            CommandSpec::Build(options) => do_cmake_build(results, options),
+            CommandSpec::Clean => {
+                results.par_map(|app| {
+                    crate::util::default_build_clean(&app.output_root)?;
+                    Ok(())
+                });
+            }
+            _ => todo!(),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_uc_apps_share_one_project() {
+    let package = tempfile::tempdir().unwrap();
+    let apps: Vec<App> = [("robot", "Robot"), ("drone", "Drone")]
+        .into_iter()
+        .map(|(name, main)| {
+            let file: crate::package::AppFile = toml::from_str(&format!(
+                "name = \"{name}\"\ntarget = \"UC\"\nmain = \"src/{main}.lf\"\nproperties = {{}}"
+            ))
+            .unwrap();
+            let app = file.convert(name, package.path());
+            fs::create_dir_all(app_dir(&app)).unwrap();
+            fs::write(app_dir(&app).join("CMakeLists.txt"), "project(App)\n").unwrap();
+            app
+        })
+        .collect();
+    let runtime = package.path().join("reactor-uc");
+    fs::create_dir_all(&runtime).unwrap();
+
+    // the stand-in records its arguments and builds the requested targets
+    let cmake = "echo \"$@\" >> \"$0.log\"\n\
+                 if [ \"$1\" = --build ]; then build=\"$2\"; shift 2\n\
+                 while [ $# -gt 0 ]; do\n\
+                 [ \"$1\" = --target ] && mkdir -p \"$build/$2\" && touch \"$build/$2/$2\"; shift\n\
+                 done; fi";
+    let (errors, log) = super::with_fake_tools(&[("lfc", ""), ("cmake", cmake)], |bin| {
+        let build = || {
+            let mut results = BatchBuildResults::for_apps(&apps.iter().collect::<Vec<_>>());
+            CmakeUc.execute_command(
+                &CommandSpec::Build(super::test_build_options(bin)),
+                &mut results,
+            );
+            results
+                .results
+                .into_iter()
+                .filter_map(|(_, result)| result.err().map(|error| error.to_string()))
+                .collect::<Vec<_>>()
+        };
+        std::env::remove_var(REACTOR_UC_PATH);
+        let mut errors = build();
+        std::env::set_var(REACTOR_UC_PATH, &runtime);
+        errors.extend(build());
+        fs::write(runtime.join("CMakeLists.txt"), "project(reactor-uc)\n").unwrap();
+        errors.extend(build());
+        std::env::remove_var(REACTOR_UC_PATH);
+        (errors, fs::read_to_string(bin.join("cmake.log")).unwrap())
+    });
+
+    assert_eq!(errors.len(), 4);
+    assert!(errors[0].starts_with("Set REACTOR_UC_PATH to the directory of the runtime"));
+    assert!(errors[2].contains("doesn't contain the CMakeLists.txt of the runtime"));
+
+    // one configuration and one build for all apps
+    let build_dir = build_dir(&apps[0].output_root);
+    let log: Vec<&str> = log.lines().collect();
+    assert_eq!(log.len(), 2);
+    assert!(log[0].contains(&format!("-DREACTOR_UC_PATH={}", runtime.display())));
+    assert!(log[0].ends_with(&format!(
+        "-S {} -B {}",
+        apps[0].src_gen_dir().display(),
+        build_dir.display()
+    )));
+    assert_eq!(
+        log[1],
+        format!(
+            "--build {} --target Robot --target Drone",
+            build_dir.display()
+        )
+    );
+    let project = fs::read_to_string(apps[0].src_gen_dir().join("CMakeLists.txt")).unwrap();
+    assert!(project.contains("add_subdirectory(Robot)\nadd_subdirectory(Drone)\n"));
+    assert!(apps[0].executable_path().exists());
+    assert!(apps[1].executable_path().exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_uc_mixed_toolchains() {
+    let package = tempfile::tempdir().unwrap();
+    let apps: Vec<App> = [("robot", "Robot", ""), ("drone", "Drone", "\n[toolchain]\ncc = \"clang\"")]
+        .into_iter()
+        .map(|(name, main, toolchain)| {
+            let file: crate::package::AppFile = toml::from_str(&format!(
+                "name = \"{name}\"\ntarget = \"UC\"\nmain = \"src/{main}.lf\"\nproperties = {{}}{toolchain}"
+            ))
+            .unwrap();
+            let app = file.convert(name, package.path());
+            fs::create_dir_all(app_dir(&app)).unwrap();
+            fs::write(app_dir(&app).join("CMakeLists.txt"), "project(App)\n").unwrap();
+            app
+        })
+        .collect();
+    assert_eq!(apps[0].output_root, apps[1].output_root);
+    let runtime = package.path().join("reactor-uc");
+    fs::create_dir_all(&runtime).unwrap();
+    fs::write(runtime.join("CMakeLists.txt"), "project(reactor-uc)\n").unwrap();
+
+    let (errors, configured) = super::with_fake_tools(
+        &[("lfc", ""), ("cmake", "echo \"$@\" >> \"$0.log\"")],
+        |bin| {
+            std::env::set_var(REACTOR_UC_PATH, &runtime);
+            let mut results = BatchBuildResults::for_apps(&apps.iter().collect::<Vec<_>>());
+            CmakeUc.execute_command(
+                &CommandSpec::Build(super::test_build_options(bin)),
+                &mut results,
+            );
+            std::env::remove_var(REACTOR_UC_PATH);
+            let errors = results
+                .results
+                .into_iter()
+                .filter_map(|(_, result)| result.err().map(|error| error.to_string()))
+                .collect::<Vec<_>>();
+            (errors, bin.join("cmake.log").exists())
+        },
+    );
+
+    // neither app is built with the compilers of the other
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].contains("have different toolchains but share the output directory"));
+    assert!(!configured);
+}
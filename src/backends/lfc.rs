@@ -1,13 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt::Display;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{fmt, fs};
 
 use serde_derive::Serialize;
 
+use crate::args::TargetLanguage;
 use crate::backends::{BatchBackend, BatchBuildResults, BuildCommandOptions, CommandSpec};
-use crate::package::App;
+use crate::package::{management::copy_dir_all, App};
 use crate::util::errors::BuildResult;
 
 pub struct LFC;
@@ -37,10 +38,104 @@ impl LFC {
             "--json={}",
             LfcJsonArgs::new(app, compile_target_code)
         ));
-        crate::util::execute_command_to_build_result(lfc_command)
+        crate::util::execute_command_to_build_result(lfc_command)?;
+
+        if app.target == TargetLanguage::Python {
+            LFC::add_python_libraries(app)?;
+        }
+        Ok(())
+    }
+
+    /// adds the pip requirements of the libraries to the requirements.txt of the generated
+    /// program and copies their Python modules next to it, so they can be imported
+    fn add_python_libraries(app: &App) -> BuildResult {
+        let properties = &app.properties;
+        let program = app.src_gen_dir().join(&app.main_reactor_name);
+
+        if !properties.pip_requirements.is_empty() {
+            let requirements_file = program.join("requirements.txt");
+            let mut requirements: Vec<String> = fs::read_to_string(&requirements_file)
+                .unwrap_or_default()
+                .lines()
+                .map(str::to_string)
+                .collect();
+            for requirement in &properties.pip_requirements {
+                if !requirements.contains(requirement) {
+                    requirements.push(requirement.clone());
+                }
+            }
+            fs::write(&requirements_file, requirements.join("\n") + "\n")?;
+        }
+
+        copy_python_modules(&properties.python_path, &program)
     }
 }
 
+/// lists the modules lingo copied into the generated program, they are replaced by later builds
+const COPIED_MODULES: &str = ".lingo-python-path";
+
+/// adds the files inside the folder to `files`, relative to the folder
+fn module_files(folder: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(folder.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            module_files(folder, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Copies the Python modules of the libraries next to the main script of the generated program,
+/// whose directory is on the module search path. Modules may neither replace files that lfc
+/// generated nor modules of another library.
+fn copy_python_modules(folders: &[PathBuf], program: &Path) -> BuildResult {
+    let manifest = program.join(COPIED_MODULES);
+    let previous: BTreeSet<PathBuf> = fs::read_to_string(&manifest)
+        .unwrap_or_default()
+        .lines()
+        .map(PathBuf::from)
+        .collect();
+
+    let mut copied: BTreeMap<PathBuf, &Path> = BTreeMap::new();
+    for folder in folders {
+        let mut files = Vec::new();
+        module_files(folder, Path::new(""), &mut files)?;
+        for file in files {
+            if let Some(other) = copied.insert(file.clone(), folder) {
+                return Err(anyhow::anyhow!(
+                    "{} is provided by the python-path folders {} and {}",
+                    file.display(),
+                    other.display(),
+                    folder.display()
+                )
+                .into());
+            }
+            if program.join(&file).exists() && !previous.contains(&file) {
+                return Err(anyhow::anyhow!(
+                    "{} from the python-path folder {} would replace a generated file of {}",
+                    file.display(),
+                    folder.display(),
+                    program.display()
+                )
+                .into());
+            }
+        }
+    }
+
+    for folder in folders {
+        copy_dir_all(folder, program)?;
+    }
+    let listing: String = copied
+        .keys()
+        .map(|file| format!("{}\n", file.display()))
+        .collect();
+    fs::write(manifest, listing)?;
+    Ok(())
+}
+
 impl BatchBackend for LFC {
     fn execute_command(&mut self, command: &CommandSpec, results: &mut BatchBuildResults) {
         match command {
@@ -58,7 +153,8 @@ impl BatchBackend for LFC {
     }
 }
 
-/// Formats LFC arguments to JSON.
+/// Formats LFC arguments to JSON. lfc reads no include directories from them, it finds the
+/// libraries in the lfc_include folder of the output directory.
 #[derive(Serialize, Clone)]
 pub struct LfcJsonArgs<'a> {
     /// Path to the LF source file containing the main reactor.
@@ -114,3 +210,63 @@ impl<'a> Display for LfcJsonArgs<'a> {
         write!(f, "{}", string)
     }
 }
+
+#[test]
+fn test_copy_python_modules() {
+    let root = tempfile::tempdir().unwrap();
+    let program = root.path().join("src-gen/Main");
+    fs::create_dir_all(&program).unwrap();
+    fs::write(program.join("Main.py"), "# generated").unwrap();
+    let library = |name: &str, module: &str| {
+        let folder = root.path().join(name);
+        fs::create_dir_all(folder.join("mqtt")).unwrap();
+        fs::write(folder.join(module), name).unwrap();
+        folder
+    };
+    let client = library("client", "mqtt/client.py");
+
+    copy_python_modules(std::slice::from_ref(&client), &program).unwrap();
+    assert_eq!(
+        fs::read_to_string(program.join("mqtt/client.py")).unwrap(),
+        "client"
+    );
+    // the modules of the previous build are replaced
+    copy_python_modules(std::slice::from_ref(&client), &program).unwrap();
+
+    let shadowing = library("shadowing", "Main.py");
+    let error = copy_python_modules(&[shadowing], &program).err().unwrap();
+    assert!(error.to_string().contains("would replace a generated file"));
+    assert_eq!(
+        fs::read_to_string(program.join("Main.py")).unwrap(),
+        "# generated"
+    );
+
+    let other = library("other", "mqtt/client.py");
+    let error = copy_python_modules(&[client, other], &program)
+        .err()
+        .unwrap();
+    assert!(error
+        .to_string()
+        .contains("is provided by the python-path folders"));
+}
+
+#[test]
+fn test_lfc_invocation() {
+    let file: crate::package::AppFile =
+        toml::from_str("name = \"robot\"\ntarget = \"C\"\nmain = \"src/Main.lf\"\nproperties = {}")
+            .unwrap();
+    let mut app = file.convert("robot", Path::new("/work/robot"));
+    app.include_paths.insert(
+        "mqtt".to_string(),
+        PathBuf::from("/work/robot/build/lfc_include/mqtt/src/lib"),
+    );
+
+    // the include directories of the libraries aren't passed, lfc has no key for them
+    let json: serde_json::Value =
+        serde_json::from_str(&LfcJsonArgs::new(&app, false).to_string()).unwrap();
+    let keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["out", "properties", "src"]);
+    assert_eq!(json["src"], "/work/robot/src/Main.lf");
+    assert_eq!(json["out"], "/work/robot/build");
+    assert_eq!(json["properties"]["no-compile"], true);
+}
@@ -2,22 +2,32 @@ use log::error;
 use rayon::prelude::*;
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::sync::Arc;
 
-use crate::args::{BuildSystem, Platform, TargetLanguage};
+use crate::args::{BuildSystem, TargetLanguage};
 use crate::package::{
-    management::DependencyManager, target_properties::MergeTargetProperties, App, Config,
-    OUTPUT_DIRECTORY,
+    features::FeatureRequest,
+    license,
+    management::{DependencyManager, ResolutionOptions},
+    target_properties::MergeTargetProperties,
+    App, Config, OUTPUT_DIRECTORY,
 };
+use crate::util::analyzer;
 use crate::util::errors::{AnyError, BuildResult, LingoError};
-use crate::{GitCloneAndCheckoutCap, WhichCapability};
+use crate::{DownloadCapability, GitCloneAndCheckoutCap, GitListTagsCap, WhichCapability};
 
+pub mod cargo;
 pub mod cmake_c;
 pub mod cmake_cpp;
+pub mod cmake_uc;
 pub mod lfc;
 pub mod npm;
+pub mod pico;
 pub mod pnpm;
+pub mod python;
+pub mod west;
 
 #[allow(clippy::single_match)] // there more options will be added to this match block
 pub fn execute_command<'a>(
@@ -25,32 +35,69 @@ pub fn execute_command<'a>(
     config: &'a mut Config,
     which: WhichCapability,
     clone: GitCloneAndCheckoutCap,
+    download: DownloadCapability,
+    list_tags: GitListTagsCap,
 ) -> BatchBuildResults<'a> {
     let mut result = BatchBuildResults::new();
-    let dependencies = Vec::from_iter(config.dependencies.clone());
-
     match command {
-        CommandSpec::Build(_build) => {
+        CommandSpec::Build(build) => {
+            let dependencies = match config.enable_features(&build.features) {
+                Ok(value) => value,
+                Err(e) => {
+                    return BatchBuildResults::failed(anyhow::anyhow!(
+                        "cannot enable the features of the dependencies because of {e}"
+                    ));
+                }
+            };
+            let declared: Vec<String> = dependencies.iter().map(|(name, _)| name.clone()).collect();
             let manager = match DependencyManager::from_dependencies(
-                dependencies.clone(),
+                dependencies,
                 &PathBuf::from(OUTPUT_DIRECTORY),
+                build.resolution,
+                config.registry.as_ref(),
+                &config.patches,
                 &clone,
+                &download,
+                &list_tags,
             ) {
                 Ok(value) => value,
                 Err(e) => {
-                    error!("failed to create dependency manager because of {e}");
-                    return result;
+                    return BatchBuildResults::failed(anyhow::anyhow!(
+                        "failed to create dependency manager because of {e}"
+                    ));
                 }
             };
 
+            if let Err(e) = license::check(&manager.licenses(), &config.policy) {
+                return BatchBuildResults::failed(anyhow::anyhow!("{e}"));
+            }
+
             // enriching the apps with the target properties from the libraries
             let library_properties = manager.get_target_properties().expect("lib properties");
+            let include_paths = match manager.include_paths(&PathBuf::from(OUTPUT_DIRECTORY)) {
+                Ok(value) => value,
+                Err(e) => {
+                    return BatchBuildResults::failed(anyhow::anyhow!(
+                        "cannot locate the libraries because of {e}"
+                    ));
+                }
+            };
 
             // merging app with library target properties
             for app in &mut config.apps {
                 if let Err(e) = app.properties.merge(&library_properties) {
-                    error!("cannot merge properties from the libraries with the app. error: {e}");
-                    return result;
+                    return BatchBuildResults::failed(anyhow::anyhow!(
+                        "cannot merge properties from the libraries with the app. error: {e}"
+                    ));
+                }
+                if let Err(e) = app.properties.copy_artifacts(&app.output_root) {
+                    return BatchBuildResults::failed(anyhow::anyhow!(
+                        "cannot copy the artifacts of the libraries. error: {e}"
+                    ));
+                }
+                app.include_paths = include_paths.clone();
+                if let Err(e) = check_imports(app, &declared) {
+                    return BatchBuildResults::failed(anyhow::anyhow!("{e}"));
                 }
             }
         }
@@ -69,15 +116,6 @@ pub fn execute_command<'a>(
     for (build_system, apps) in by_build_system {
         let mut sub_res = BatchBuildResults::for_apps(&apps);
 
-        sub_res.map(|app| {
-            // TODO: Support using lingo as a thin wrapper around west
-            if app.platform == Platform::Zephyr {
-                Err(Box::new(LingoError::UseWestBuildToBuildApp))
-            } else {
-                Ok(())
-            }
-        });
-
         match build_system {
             (BuildSystem::CMake, TargetLanguage::Cpp) => {
                 cmake_cpp::CmakeCpp.execute_command(command, &mut sub_res)
@@ -85,14 +123,22 @@ pub fn execute_command<'a>(
             (BuildSystem::CMake, TargetLanguage::C) => {
                 cmake_c::CmakeC.execute_command(command, &mut sub_res)
             }
+            (BuildSystem::CMake, TargetLanguage::UC) => {
+                cmake_uc::CmakeUc.execute_command(command, &mut sub_res)
+            }
             (BuildSystem::Npm, TargetLanguage::TypeScript) => {
                 npm::Npm.execute_command(command, &mut sub_res)
             }
             (BuildSystem::Pnpm, TargetLanguage::TypeScript) => {
                 pnpm::Pnpm.execute_command(command, &mut sub_res)
             }
+            (BuildSystem::Pip, TargetLanguage::Python) => {
+                python::Python.execute_command(command, &mut sub_res)
+            }
+            (BuildSystem::West, _) => west::West.execute_command(command, &mut sub_res),
+            (BuildSystem::Pico, _) => pico::Pico.execute_command(command, &mut sub_res),
             (BuildSystem::LFC, _) => lfc::LFC.execute_command(command, &mut sub_res),
-            (BuildSystem::Cargo, _) => todo!(),
+            (BuildSystem::Cargo, _) => cargo::Cargo.execute_command(command, &mut sub_res),
             _ => {
                 error!("invalid combination of target and platform!");
                 todo!()
@@ -103,6 +149,90 @@ pub fn execute_command<'a>(
     result
 }
 
+/// Every library the LF files of the app import from has to be a dependency of the package
+/// and contain the imported file. lfc looks the file up in the include folder of the library.
+fn check_imports(app: &App, declared: &[String]) -> BuildResult {
+    let Some(src) = app.src_dir_path() else {
+        return Ok(());
+    };
+    for import in analyzer::find_library_imports(&src)? {
+        if !declared.contains(&import.library) {
+            return Err(Box::new(LingoError::UndeclaredImport(
+                import.library,
+                import.path,
+            )));
+        }
+        if let Some(library) = app.include_paths.get(&import.library) {
+            if !library.join(&import.file).is_file() {
+                return Err(Box::new(LingoError::MissingImport(
+                    import.library,
+                    import.file,
+                    import.path,
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Location of a runtime or SDK the apps are compiled against, taken from the environment
+/// variable. The directory has to contain the `marker` file.
+fn runtime_path(variable: &str, marker: &str) -> Result<PathBuf, Box<AnyError>> {
+    let Some(path) = std::env::var_os(variable).map(PathBuf::from) else {
+        return Err(Box::new(LingoError::MissingRuntime(variable.to_string())));
+    };
+    if !path.join(marker).exists() {
+        return Err(Box::new(LingoError::InvalidRuntime(
+            variable.to_string(),
+            path,
+            marker.to_string(),
+        )));
+    }
+    Ok(path)
+}
+
+/// passes the toolchain of the app to a cmake configure command
+fn apply_toolchain(cmake: &mut Command, app: &App) {
+    let Some(toolchain) = &app.toolchain else {
+        return;
+    };
+    if let Some(file) = &toolchain.file {
+        cmake.arg(format!("-DCMAKE_TOOLCHAIN_FILE={}", file.display()));
+    }
+    // only clang takes the target as an argument, gcc is built for a single target
+    let is_clang = |compiler: &Option<String>| {
+        compiler.as_ref().is_some_and(|compiler| {
+            Path::new(compiler)
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().contains("clang"))
+        })
+    };
+    if let Some(triple) = &toolchain.triple {
+        if is_clang(&toolchain.cc) {
+            cmake.arg(format!("-DCMAKE_C_COMPILER_TARGET={triple}"));
+        }
+        if is_clang(&toolchain.cxx) {
+            cmake.arg(format!("-DCMAKE_CXX_COMPILER_TARGET={triple}"));
+        }
+    }
+    if let Some(cc) = &toolchain.cc {
+        cmake.arg(format!("-DCMAKE_C_COMPILER={cc}"));
+        cmake.env("CC", cc);
+    }
+    if let Some(cxx) = &toolchain.cxx {
+        cmake.arg(format!("-DCMAKE_CXX_COMPILER={cxx}"));
+        cmake.env("CXX", cxx);
+    }
+    if let Some(sysroot) = &toolchain.sysroot {
+        cmake.arg(format!("-DCMAKE_SYSROOT={}", sysroot.display()));
+    }
+    if !toolchain.flags.is_empty() {
+        let flags = toolchain.flags.join(" ");
+        cmake.arg(format!("-DCMAKE_C_FLAGS={flags}"));
+        cmake.arg(format!("-DCMAKE_CXX_FLAGS={flags}"));
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub enum BuildProfile {
     /// Compile with optimizations.
@@ -124,6 +254,10 @@ pub struct BuildCommandOptions {
     pub max_threads: usize,
     /// if compilation should continue if one of the apps fails building
     pub keep_going: bool,
+    /// restrictions on how dependencies are resolved
+    pub resolution: ResolutionOptions,
+    /// features of the package that are enabled
+    pub features: FeatureRequest,
 }
 
 /// Description of a lingo command
@@ -145,6 +279,8 @@ pub trait BatchBackend {
 /// Collects build results by app.
 pub struct BatchBuildResults<'a> {
     results: Vec<(&'a App, BuildResult)>,
+    /// error that stopped the command before the apps were built
+    failure: Option<Box<AnyError>>,
     keep_going: bool,
 }
 
@@ -153,15 +289,25 @@ impl<'a> BatchBuildResults<'a> {
     fn new() -> Self {
         Self {
             results: Vec::new(),
+            failure: None,
             keep_going: false,
         }
     }
 
+    /// Create a result for a command that failed before any app was built.
+    fn failed(error: impl Into<Box<AnyError>>) -> Self {
+        Self {
+            failure: Some(error.into()),
+            ..Self::new()
+        }
+    }
+
     /// Create a result with an entry for each app. This can
     /// then be used by combinators like map and such.
     fn for_apps(apps: &[&'a App]) -> Self {
         Self {
             results: apps.iter().map(|&a| (a, Ok(()))).collect(),
+            failure: None,
             keep_going: false,
         }
     }
@@ -170,8 +316,16 @@ impl<'a> BatchBuildResults<'a> {
         self.keep_going = value
     }
 
+    /// Whether the command failed, or any app failed to build.
+    pub fn is_err(&self) -> bool {
+        self.failure.is_some() || self.results.iter().any(|(_, result)| result.is_err())
+    }
+
     /// Print this result collection to standard output.
     pub fn print_results(&self) {
+        if let Some(e) = &self.failure {
+            log::error!("{e}");
+        }
         for (app, b) in &self.results {
             match b {
                 Ok(()) => {
@@ -188,6 +342,7 @@ impl<'a> BatchBuildResults<'a> {
     /// is only ok if the other is disjoint from this result.
     fn append(&mut self, mut other: BatchBuildResults<'a>) {
         self.results.append(&mut other.results);
+        self.failure = self.failure.take().or(other.failure);
         self.results.sort_by_key(|(app, _)| &app.name);
     }
 
@@ -277,3 +432,126 @@ impl<'a> BatchBuildResults<'a> {
         self
     }
 }
+
+/// The PATH is shared by the whole test process, tests that put stand-in tools on it hold this.
+#[cfg(all(test, unix))]
+static PATH_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Runs `run` with shell scripts standing in for the given tools first on the PATH. The scripts
+/// are placed in the folder that is passed to `run`, so they can leave their logs there.
+#[cfg(all(test, unix))]
+pub(crate) fn with_fake_tools<T>(tools: &[(&str, &str)], run: impl FnOnce(&Path) -> T) -> T {
+    use std::os::unix::fs::PermissionsExt;
+
+    let _guard = PATH_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let bin = tempfile::tempdir().unwrap();
+    for (name, script) in tools {
+        let tool = bin.path().join(name);
+        std::fs::write(&tool, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let search = std::iter::once(bin.path().to_path_buf()).chain(std::env::split_paths(&path));
+    std::env::set_var("PATH", std::env::join_paths(search).unwrap());
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run(bin.path())));
+    std::env::set_var("PATH", path);
+    result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Options of a debug build that runs the stand-in `lfc` of `with_fake_tools`.
+#[cfg(all(test, unix))]
+pub(crate) fn test_build_options(bin: &Path) -> BuildCommandOptions {
+    BuildCommandOptions {
+        profile: BuildProfile::Debug,
+        compile_target_code: true,
+        lfc_exec_path: bin.join("lfc"),
+        max_threads: 0,
+        keep_going: true,
+        resolution: ResolutionOptions::default(),
+        features: FeatureRequest::default_features(),
+    }
+}
+
+#[test]
+fn test_check_imports() {
+    let package = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(package.path().join("src")).unwrap();
+    std::fs::write(
+        package.path().join("src/Main.lf"),
+        "target C\nimport Client from <mqtt/Client.lf>\nmain reactor {}",
+    )
+    .unwrap();
+    let file: crate::package::AppFile =
+        toml::from_str("name = \"robot\"\ntarget = \"C\"\nmain = \"src/Main.lf\"\nproperties = {}")
+            .unwrap();
+    let mut app = file.convert("robot", package.path());
+    let library = package.path().join("build/lfc_include/mqtt/src/lib");
+    std::fs::create_dir_all(&library).unwrap();
+    app.include_paths
+        .insert("mqtt".to_string(), library.clone());
+    let declared = ["mqtt".to_string()];
+
+    let error = check_imports(&app, &[]).unwrap_err();
+    assert!(error.to_string().contains("not a dependency"));
+    let error = check_imports(&app, &declared).unwrap_err();
+    assert!(error.to_string().contains("doesn't contain it"));
+
+    std::fs::write(library.join("Client.lf"), "target C\nreactor Client {}").unwrap();
+    check_imports(&app, &declared).unwrap();
+}
+
+#[test]
+fn test_apply_toolchain() {
+    let file: crate::package::AppFile = toml::from_str(
+        "name = \"robot\"\ntarget = \"C\"\nmain = \"src/Main.lf\"\nproperties = {}\n\
+         [toolchain]\ntriple = \"aarch64-linux-gnu\"\ncc = \"/usr/bin/clang-18\"\ncxx = \"g++\"",
+    )
+    .unwrap();
+    let mut app = file.convert("robot", Path::new("/work/robot"));
+    let args = |app: &App| {
+        let mut cmake = Command::new("cmake");
+        apply_toolchain(&mut cmake, app);
+        cmake
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // the triple is only passed to clang
+    assert_eq!(
+        args(&app),
+        [
+            "-DCMAKE_C_COMPILER_TARGET=aarch64-linux-gnu",
+            "-DCMAKE_C_COMPILER=/usr/bin/clang-18",
+            "-DCMAKE_CXX_COMPILER=g++",
+        ]
+    );
+    app.toolchain.as_mut().unwrap().cc = None;
+    assert_eq!(args(&app), ["-DCMAKE_CXX_COMPILER=g++"]);
+}
+
+#[test]
+fn test_failed_results() {
+    let file: crate::package::AppFile =
+        toml::from_str("name = \"robot\"\ntarget = \"C\"\nmain = \"src/Main.lf\"\nproperties = {}")
+            .unwrap();
+    let app = file.convert("robot", Path::new("/work/robot"));
+
+    let mut results = BatchBuildResults::new();
+    results.append(BatchBuildResults::for_apps(&[&app]));
+    assert!(!results.is_err());
+
+    // a command that fails before building, like a --locked build with an outdated Lingo.lock
+    results.append(BatchBuildResults::failed(anyhow::anyhow!(
+        "Lingo.lock is outdated"
+    )));
+    assert!(results.is_err());
+
+    let mut results = BatchBuildResults::for_apps(&[&app]);
+    results.keep_going(true);
+    results.map(|_| Err(anyhow::anyhow!("compilation failed").into()));
+    assert!(results.is_err());
+}
@@ -1,5 +1,7 @@
-use crate::util::errors::LingoError;
+use crate::package::management::copy_dir_all;
+use crate::util::errors::{BuildResult, LingoError};
 use crate::util::execute_command_to_build_result;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::ffi::OsString;
 use std::fs;
@@ -18,6 +20,99 @@ use crate::backends::{
     BatchBackend, BatchBuildResults, BuildCommandOptions, BuildProfile, CommandSpec,
 };
 
+/// lists the npm packages lingo added to the package.json, they are replaced by later builds
+const ADDED_DEPENDENCIES: &str = ".lingo-npm-dependencies";
+
+/// lists the TypeScript source folders lingo copied into the src folder of the project
+const COPIED_SOURCES: &str = ".lingo-ts-sources";
+
+/// reads the entries a previous build recorded in the manifest
+fn read_manifest(manifest: &Path) -> BTreeSet<String> {
+    fs::read_to_string(manifest)
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Adds the npm packages of the libraries to the package.json of the generated project. They
+/// may not replace a package the generated code depends on, like its runtime.
+fn add_npm_dependencies(dependencies: &BTreeMap<String, String>, project: &Path) -> BuildResult {
+    let manifest = project.join(ADDED_DEPENDENCIES);
+    let previous = read_manifest(&manifest);
+
+    let package_json = project.join("package.json");
+    let mut package: serde_json::Value = serde_json::from_str(&fs::read_to_string(&package_json)?)?;
+    let entries = package
+        .as_object_mut()
+        .ok_or(LingoError::InvalidPackageJson(package_json.clone()))?
+        .entry("dependencies")
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or(LingoError::InvalidPackageJson(package_json.clone()))?;
+    for (name, version) in dependencies {
+        match entries.get(name).and_then(|generated| generated.as_str()) {
+            Some(generated) if generated != version && !previous.contains(name) => {
+                return Err(anyhow::anyhow!(
+                    "the libraries depend on the npm package {name} {version}, but the generated code depends on {name} {generated}"
+                )
+                .into());
+            }
+            _ => {
+                entries.insert(name.clone(), serde_json::Value::String(version.clone()));
+            }
+        }
+    }
+    fs::write(&package_json, serde_json::to_string_pretty(&package)?)?;
+
+    let listing: String = dependencies
+        .keys()
+        .map(|name| format!("{name}\n"))
+        .collect();
+    fs::write(manifest, listing)?;
+    Ok(())
+}
+
+/// Copies the TypeScript source folders of the libraries into the src folder of the generated
+/// project. They may neither replace generated sources nor the folder of another library.
+fn copy_ts_sources(folders: &[PathBuf], project: &Path) -> BuildResult {
+    let manifest = project.join(COPIED_SOURCES);
+    let previous = read_manifest(&manifest);
+
+    let mut copied: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for folder in folders {
+        let folder = fs::canonicalize(folder)?;
+        let name = folder
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some(other) = copied.get(&name).filter(|other| **other != folder) {
+            return Err(anyhow::anyhow!(
+                "the ts-sources folders {} and {} would both be copied to src/{name}",
+                other.display(),
+                folder.display()
+            )
+            .into());
+        }
+        if project.join("src").join(&name).exists() && !previous.contains(&name) {
+            return Err(anyhow::anyhow!(
+                "the ts-sources folder {} would replace the generated src/{name} of {}",
+                folder.display(),
+                project.display()
+            )
+            .into());
+        }
+        copied.insert(name, folder);
+    }
+
+    for (name, folder) in &copied {
+        copy_dir_all(folder, project.join("src").join(name))?;
+    }
+    let listing: String = copied.keys().map(|name| format!("{name}\n")).collect();
+    fs::write(manifest, listing)?;
+    Ok(())
+}
+
 pub fn do_typescript_build(
     results: &mut BatchBuildResults,
     options: &BuildCommandOptions,
@@ -67,6 +162,10 @@ pub fn do_typescript_build(
         .map(|app| {
             let src_postfix = extract_location(&app.main_reactor, &app.root_path)?;
             let path = app.output_root.join("src-gen").join(src_postfix);
+            if !app.properties.npm_dependencies.is_empty() {
+                add_npm_dependencies(&app.properties.npm_dependencies, &path)?;
+            }
+            copy_ts_sources(&app.properties.ts_sources, &path)?;
 
             let mut npm_install = Command::new(commands.binary_name);
             npm_install.current_dir(path);
@@ -136,3 +235,48 @@ impl BatchBackend for Npm {
         }
     }
 }
+
+#[test]
+fn test_library_conflicts() {
+    let package = tempfile::tempdir().unwrap();
+    let project = package.path().join("project");
+    fs::create_dir_all(project.join("src").join("generated")).unwrap();
+    fs::write(
+        project.join("package.json"),
+        r#"{"dependencies": {"@lf-lang/reactor-ts": "0.6.0"}}"#,
+    )
+    .unwrap();
+    let folder = |path: &str| {
+        let folder = package.path().join(path);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("lib.ts"), "export {}").unwrap();
+        folder
+    };
+    let (websocket, other, generated) = (folder("a/ws"), folder("b/ws"), folder("generated"));
+
+    // the runtime of the generated code can't be replaced, other packages are added
+    let runtime = BTreeMap::from([("@lf-lang/reactor-ts".to_string(), "0.7.0".to_string())]);
+    let error = add_npm_dependencies(&runtime, &project).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("generated code depends on @lf-lang/reactor-ts 0.6.0"));
+    let ws = BTreeMap::from([("ws".to_string(), "^8.0.0".to_string())]);
+    add_npm_dependencies(&ws, &project).unwrap();
+    let ws = BTreeMap::from([("ws".to_string(), "^8.1.0".to_string())]);
+    add_npm_dependencies(&ws, &project).unwrap();
+    let package_json = fs::read_to_string(project.join("package.json")).unwrap();
+    assert!(package_json.contains("\"@lf-lang/reactor-ts\": \"0.6.0\""));
+    assert!(package_json.contains("\"ws\": \"^8.1.0\""));
+
+    let error = copy_ts_sources(&[websocket.clone(), other], &project).unwrap_err();
+    assert!(error.to_string().contains("would both be copied to src/ws"));
+    let error = copy_ts_sources(&[generated], &project).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("would replace the generated src/generated"));
+
+    // a rebuild replaces the folder copied by the previous build
+    copy_ts_sources(std::slice::from_ref(&websocket), &project).unwrap();
+    copy_ts_sources(&[websocket], &project).unwrap();
+    assert!(project.join("src").join("ws").join("lib.ts").exists());
+}
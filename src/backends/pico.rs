@@ -0,0 +1,168 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::backends::{
+    BatchBackend, BatchBuildResults, BuildCommandOptions, BuildProfile, BuildResult, CommandSpec,
+};
+use crate::package::App;
+use crate::util::errors::LingoError;
+use crate::util::execute_command_to_build_result;
+
+pub struct Pico;
+
+/// environment variable pointing to a checkout of the pico-sdk
+const PICO_SDK_PATH: &str = "PICO_SDK_PATH";
+
+/// cmake build directory of the app
+fn build_dir(app: &App) -> PathBuf {
+    app.output_root.join("pico").join(&app.name)
+}
+
+fn configure(app: &App, options: &BuildCommandOptions) -> BuildResult {
+    let sdk = super::runtime_path(PICO_SDK_PATH, "pico_sdk_init.cmake")?;
+
+    // location of the cmake file
+    let app_build_folder = app.src_gen_dir().join(&app.main_reactor_name);
+    let cmake_file = app_build_folder.join("CMakeLists.txt");
+
+    // create potential files that come from the target properties
+    app.properties.write_artifacts(&app_build_folder)?;
+
+    // read file and append cmake include to generated cmake file
+    let mut content = fs::read_to_string(&cmake_file)?;
+    content += "\ninclude(./aggregated_cmake_include.cmake)";
+
+    // overwrite cmake file
+    let mut f = fs::OpenOptions::new().write(true).open(&cmake_file)?;
+    f.write_all(content.as_ref())?;
+    f.flush()?;
+
+    let mut cmake = Command::new("cmake");
+    cmake.arg(format!(
+        "-DCMAKE_BUILD_TYPE={}",
+        if options.profile == BuildProfile::Release {
+            "RELEASE"
+        } else {
+            "DEBUG"
+        }
+    ));
+    cmake.arg(format!("-D{PICO_SDK_PATH}={}", sdk.display()));
+    cmake.env(PICO_SDK_PATH, &sdk);
+    if let Some(board) = &app.board {
+        cmake.arg(format!("-DPICO_BOARD={board}"));
+    }
+    cmake.arg("-S").arg(&app_build_folder);
+    cmake.arg("-B").arg(build_dir(app));
+    execute_command_to_build_result(cmake)
+}
+
+fn build(app: &App, options: &BuildCommandOptions) -> BuildResult {
+    let mut cmake = Command::new("cmake");
+    cmake.arg("--build").arg(build_dir(app));
+    if options.max_threads != 0 {
+        cmake.arg("--parallel");
+        cmake.arg(options.max_threads.to_string());
+    }
+    execute_command_to_build_result(cmake)
+}
+
+/// copies the firmware images to `build/bin/<app>.uf2` and `build/bin/<app>.elf`
+fn collect_images(app: &App) -> BuildResult {
+    let image = build_dir(app).join(&app.main_reactor_name);
+    let uf2 = image.with_extension("uf2");
+    if !uf2.exists() {
+        return Err(Box::new(LingoError::MissingBinary(uf2)));
+    }
+
+    fs::create_dir_all(app.output_root.join("bin"))?;
+    fs::copy(&uf2, app.executable_path())?;
+    let elf = image.with_extension("elf");
+    if elf.exists() {
+        fs::copy(&elf, app.executable_path().with_extension("elf"))?;
+    }
+    Ok(())
+}
+
+fn do_pico_build(results: &mut BatchBuildResults, options: &BuildCommandOptions) {
+    results.keep_going(options.keep_going);
+    super::lfc::LFC::do_parallel_lfc_codegen(options, results, false);
+    if !options.compile_target_code {
+        return;
+    }
+    results
+        .map(|app| configure(app, options))
+        .map(|app| build(app, options))
+        .map(collect_images);
+}
+
+impl BatchBackend for Pico {
+    fn execute_command(&mut self, command: &CommandSpec, results: &mut BatchBuildResults) {
+        match command {
+            CommandSpec::Build(options) => do_pico_build(results, options),
+            CommandSpec::Clean => {
+                results.par_map(|app| {
+                    crate::util::default_build_clean(&app.output_root)?;
+                    crate::util::delete_subdirs(&app.output_root, &["pico"])?;
+                    Ok(())
+                });
+            }
+            _ => todo!(),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pico_build() {
+    let package = tempfile::tempdir().unwrap();
+    let file: crate::package::AppFile = toml::from_str(
+        "name = \"robot\"\ntarget = \"C\"\nplatform = \"RP2040\"\nboard = \"pico_w\"\n\
+         main = \"src/Robot.lf\"\nproperties = {}",
+    )
+    .unwrap();
+    let app = file.convert("robot", package.path());
+    fs::create_dir_all(app.src_gen_dir().join("Robot")).unwrap();
+    fs::write(
+        app.src_gen_dir().join("Robot/CMakeLists.txt"),
+        "project(Robot)\n",
+    )
+    .unwrap();
+    let sdk = package.path().join("pico-sdk");
+    fs::create_dir_all(&sdk).unwrap();
+    fs::write(sdk.join("pico_sdk_init.cmake"), "").unwrap();
+
+    // the stand-in records its arguments and writes the images into the build directory
+    let cmake = "echo \"$@\" >> \"$0.log\"\n\
+                 if [ \"$1\" = --build ]; then mkdir -p \"$2\" && touch \"$2/Robot.uf2\" \"$2/Robot.elf\"; fi";
+    let (result, log) = super::with_fake_tools(&[("lfc", ""), ("cmake", cmake)], |bin| {
+        std::env::set_var(PICO_SDK_PATH, &sdk);
+        let mut results = BatchBuildResults::for_apps(&[&app]);
+        Pico.execute_command(
+            &CommandSpec::Build(super::test_build_options(bin)),
+            &mut results,
+        );
+        std::env::remove_var(PICO_SDK_PATH);
+        let (_, result) = results.results.pop().unwrap();
+        (result, fs::read_to_string(bin.join("cmake.log")).unwrap())
+    });
+
+    result.unwrap();
+    let log: Vec<&str> = log.lines().collect();
+    assert_eq!(
+        log,
+        [
+            format!(
+                "-DCMAKE_BUILD_TYPE=DEBUG -DPICO_SDK_PATH={} -DPICO_BOARD=pico_w -S {} -B {}",
+                sdk.display(),
+                app.src_gen_dir().join("Robot").display(),
+                build_dir(&app).display()
+            ),
+            format!("--build {}", build_dir(&app).display()),
+        ]
+    );
+    assert!(app.executable_path().ends_with("bin/robot.uf2"));
+    assert!(app.executable_path().exists());
+    assert!(app.executable_path().with_extension("elf").exists());
+}
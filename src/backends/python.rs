@@ -0,0 +1,225 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::backends::{BatchBackend, BatchBuildResults, BuildCommandOptions, CommandSpec};
+use crate::package::App;
+use crate::util::errors::BuildResult;
+use crate::util::{execute_command_to_build_result, sha1dir};
+
+pub struct Python;
+
+/// interpreter used to create the virtual environment if the apps don't configure one
+const DEFAULT_PYTHON: &str = if cfg!(windows) { "python" } else { "python3" };
+
+/// the virtual environment shared by all python apps of the package
+fn venv_dir(output_root: &Path) -> PathBuf {
+    output_root.join("venv")
+}
+
+/// interpreter inside the virtual environment
+fn venv_python(output_root: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv_dir(output_root).join("Scripts").join("python.exe")
+    } else {
+        venv_dir(output_root).join("bin").join("python")
+    }
+}
+
+/// directory of the program lfc generated for the app
+fn program_dir(app: &App) -> PathBuf {
+    app.src_gen_dir().join(&app.main_reactor_name)
+}
+
+/// creates the virtual environment unless it exists already
+fn create_venv(apps: &[&App]) -> BuildResult {
+    let output_root = &apps[0].output_root;
+    if venv_python(output_root).exists() {
+        return Ok(());
+    }
+
+    let mut interpreters = apps.iter().filter_map(|app| app.properties.python.as_ref());
+    let python = interpreters
+        .next()
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_PYTHON));
+    if let Some(other) = interpreters.find(|other| **other != python) {
+        return Err(anyhow::anyhow!(
+            "the python apps use different interpreters {} and {}, but share one environment",
+            python.display(),
+            other.display()
+        )
+        .into());
+    }
+
+    let mut venv = Command::new(python);
+    venv.arg("-m").arg("venv").arg(venv_dir(output_root));
+    execute_command_to_build_result(venv)
+}
+
+/// Installs the requirements and the C extension of the generated program into the virtual
+/// environment. Nothing is installed if the generated code didn't change since the last
+/// successful installation.
+fn install_program(app: &App) -> BuildResult {
+    let python = venv_python(&app.output_root);
+    let program = program_dir(app);
+
+    let stamp = venv_dir(&app.output_root).join(format!("{}.lingo-checksum", app.name));
+    // pip leaves these behind when building the extension inside the program directory
+    let checksum =
+        sha1dir::checksum_dir_ignoring(&program, true, &["/build/", "/*.egg-info/"])?.to_string();
+    if fs::read_to_string(&stamp).unwrap_or_default() == checksum {
+        return Ok(());
+    }
+    // a failing installation must not leave the stamp of an earlier one behind
+    let _ = fs::remove_file(&stamp);
+
+    let requirements = program.join("requirements.txt");
+    if requirements.exists() {
+        let mut pip = Command::new(&python);
+        pip.args(["-m", "pip", "install", "-r"]).arg(&requirements);
+        execute_command_to_build_result(pip)?;
+    }
+
+    let mut pip = Command::new(&python);
+    pip.args(["-m", "pip", "install", "--force-reinstall"])
+        .arg(&program);
+    execute_command_to_build_result(pip)?;
+    fs::write(stamp, checksum)?;
+    Ok(())
+}
+
+/// writes a launcher that runs the generated program with the interpreter of the environment
+fn write_launcher(app: &App) -> BuildResult {
+    let python = fs::canonicalize(venv_python(&app.output_root))?;
+    let script = fs::canonicalize(program_dir(app))?.join(format!("{}.py", app.main_reactor_name));
+
+    fs::create_dir_all(app.output_root.join("bin"))?;
+    let launcher = app.executable_path();
+    if cfg!(windows) {
+        fs::write(
+            launcher.with_extension("cmd"),
+            format!(
+                "@echo off\r\n\"{}\" \"{}\" %*\r\n",
+                python.display(),
+                script.display()
+            ),
+        )?;
+    } else {
+        fs::write(
+            &launcher,
+            format!(
+                "#!/bin/sh\nexec \"{}\" \"{}\" \"$@\"\n",
+                python.display(),
+                script.display()
+            ),
+        )?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&launcher, fs::Permissions::from_mode(0o755))?;
+        }
+    }
+    Ok(())
+}
+
+fn do_python_build(results: &mut BatchBuildResults, options: &BuildCommandOptions) {
+    results.keep_going(options.keep_going);
+    super::lfc::LFC::do_parallel_lfc_codegen(options, results, false);
+    if !options.compile_target_code {
+        return;
+    }
+    results
+        .gather(|apps| create_venv(apps))
+        .map(install_program)
+        .map(write_launcher);
+}
+
+impl BatchBackend for Python {
+    fn execute_command(&mut self, command: &CommandSpec, results: &mut BatchBuildResults) {
+        match command {
+            CommandSpec::Build(options) => do_python_build(results, options),
+            CommandSpec::Clean => {
+                results.par_map(|app| {
+                    crate::util::default_build_clean(&app.output_root)?;
+                    crate::util::delete_subdirs(&app.output_root, &["venv"])?;
+                    Ok(())
+                });
+            }
+            _ => todo!(),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_python_install_stamp() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let package = tempfile::tempdir().unwrap();
+    let file: crate::package::AppFile = toml::from_str(
+        "name = \"robot\"\ntarget = \"Python\"\nmain = \"src/Main.lf\"\n\
+         properties = { python = \"tools/python\" }",
+    )
+    .unwrap();
+    let app = file.convert("robot", package.path());
+    assert_eq!(
+        app.properties.python,
+        Some(package.path().join("tools/python"))
+    );
+
+    // the interpreter copies itself into the environment, where it logs the pip invocations
+    fs::create_dir_all(package.path().join("tools")).unwrap();
+    fs::write(
+        package.path().join("tools/python"),
+        "#!/bin/sh\n\
+         if [ \"$2\" = venv ]; then mkdir -p \"$3/bin\" && cp \"$0\" \"$3/bin/python\"; exit; fi\n\
+         echo \"$@\" >> \"$0.log\"\n\
+         [ ! -e \"$0.fail\" ]",
+    )
+    .unwrap();
+    fs::set_permissions(
+        package.path().join("tools/python"),
+        fs::Permissions::from_mode(0o755),
+    )
+    .unwrap();
+    let program = program_dir(&app);
+    fs::create_dir_all(&program).unwrap();
+    fs::write(program.join("Main.py"), "print('robot')").unwrap();
+    fs::write(program.join("requirements.txt"), "numpy\n").unwrap();
+    let python = venv_python(&app.output_root);
+    let log = python.with_extension("log");
+    let build = |bin: &Path| {
+        let mut results = BatchBuildResults::for_apps(&[&app]);
+        Python.execute_command(
+            &CommandSpec::Build(super::test_build_options(bin)),
+            &mut results,
+        );
+        let (_, result) = results.results.pop().unwrap();
+        result.is_ok()
+    };
+
+    super::with_fake_tools(&[("lfc", "")], |bin| {
+        assert!(build(bin));
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 2);
+        assert!(app.executable_path().exists());
+        assert!(!program.join(sha1dir::IGNORE_FILE).exists());
+
+        // pip leftovers don't cause a reinstallation
+        fs::create_dir_all(program.join("build")).unwrap();
+        fs::write(program.join("build/robot.so"), "").unwrap();
+        assert!(build(bin));
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 2);
+
+        // a failed installation is retried by the next build
+        fs::write(program.join("Main.py"), "print('drone')").unwrap();
+        fs::write(python.with_extension("fail"), "").unwrap();
+        assert!(!build(bin));
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 3);
+        fs::remove_file(python.with_extension("fail")).unwrap();
+        assert!(build(bin));
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 5);
+        assert!(build(bin));
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 5);
+    });
+}
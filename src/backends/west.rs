@@ -0,0 +1,131 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::backends::{BatchBackend, BatchBuildResults, BuildCommandOptions, CommandSpec};
+use crate::package::App;
+use crate::util::errors::{BuildResult, LingoError};
+use crate::util::execute_command_to_build_result;
+
+pub struct West;
+
+/// build directory of west for the app
+fn build_dir(app: &App) -> PathBuf {
+    app.output_root.join("west").join(&app.name)
+}
+
+/// builds the generated zephyr application for the board of the app
+fn west_build(app: &App, options: &BuildCommandOptions) -> BuildResult {
+    let board = app
+        .board
+        .as_ref()
+        .ok_or(LingoError::MissingBoard(app.name.clone()))?;
+
+    let mut west = Command::new("west");
+    west.arg("build");
+    west.args(["-b", board]);
+    west.arg("-d").arg(build_dir(app));
+    west.args(["-p", "auto"]);
+    if options.max_threads != 0 {
+        west.arg(format!("-o=-j{}", options.max_threads));
+    }
+    west.arg(app.src_gen_dir().join(&app.main_reactor_name));
+    execute_command_to_build_result(west)
+}
+
+/// copies the firmware images to `build/bin/<app>.elf` and `build/bin/<app>.hex`
+fn collect_images(app: &App) -> BuildResult {
+    let zephyr = build_dir(app).join("zephyr");
+    let elf = zephyr.join("zephyr.elf");
+    if !elf.exists() {
+        return Err(Box::new(LingoError::MissingBinary(elf)));
+    }
+
+    fs::create_dir_all(app.output_root.join("bin"))?;
+    fs::copy(&elf, app.executable_path())?;
+    let hex = zephyr.join("zephyr.hex");
+    if hex.exists() {
+        fs::copy(&hex, app.executable_path().with_extension("hex"))?;
+    }
+    Ok(())
+}
+
+fn do_west_build(results: &mut BatchBuildResults, options: &BuildCommandOptions) {
+    results.keep_going(options.keep_going);
+    super::lfc::LFC::do_parallel_lfc_codegen(options, results, false);
+    if !options.compile_target_code {
+        return;
+    }
+    results
+        .map(|app| west_build(app, options))
+        .map(collect_images);
+}
+
+impl BatchBackend for West {
+    fn execute_command(&mut self, command: &CommandSpec, results: &mut BatchBuildResults) {
+        match command {
+            CommandSpec::Build(options) => do_west_build(results, options),
+            CommandSpec::Clean => {
+                results.par_map(|app| {
+                    crate::util::default_build_clean(&app.output_root)?;
+                    crate::util::delete_subdirs(&app.output_root, &["west"])?;
+                    Ok(())
+                });
+            }
+            _ => todo!(),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_west_build() {
+    let package = tempfile::tempdir().unwrap();
+    let apps: Vec<App> = [
+        ("robot", "Robot", "board = \"nrf52dk/nrf52832\"\n"),
+        ("drone", "Drone", ""),
+    ]
+    .into_iter()
+    .map(|(name, main, board)| {
+        let file: crate::package::AppFile = toml::from_str(&format!(
+            "name = \"{name}\"\ntarget = \"C\"\nplatform = \"Zephyr\"\n{board}\
+             main = \"src/{main}.lf\"\nproperties = {{}}"
+        ))
+        .unwrap();
+        file.convert(name, package.path())
+    })
+    .collect();
+
+    // the stand-in records its arguments and writes the images into the build directory
+    let west = "echo \"$@\" >> \"$0.log\"\n\
+                while [ $# -gt 0 ]; do [ \"$1\" = -d ] && build=\"$2\"; shift; done\n\
+                mkdir -p \"$build/zephyr\" && touch \"$build/zephyr/zephyr.elf\" \"$build/zephyr/zephyr.hex\"";
+    let (results, log) = super::with_fake_tools(&[("lfc", ""), ("west", west)], |bin| {
+        let mut results = BatchBuildResults::for_apps(&apps.iter().collect::<Vec<_>>());
+        West.execute_command(
+            &CommandSpec::Build(super::test_build_options(bin)),
+            &mut results,
+        );
+        let results: Vec<(String, BuildResult)> = results
+            .results
+            .into_iter()
+            .map(|(app, result)| (app.name.clone(), result))
+            .collect();
+        (results, fs::read_to_string(bin.join("west.log")).unwrap())
+    });
+
+    assert_eq!(
+        log.trim(),
+        format!(
+            "build -b nrf52dk/nrf52832 -d {} -p auto {}",
+            build_dir(&apps[0]).display(),
+            apps[0].src_gen_dir().join("Robot").display()
+        )
+    );
+    assert!(results[0].1.is_ok());
+    assert!(apps[0].executable_path().ends_with("bin/robot.elf"));
+    assert!(apps[0].executable_path().exists());
+    assert!(apps[0].executable_path().with_extension("hex").exists());
+    let error = results[1].1.as_ref().unwrap_err().to_string();
+    assert_eq!(error, "The app drone needs a `board` in the Lingo.toml");
+}
@@ -1,4 +1,4 @@
-use crate::package::tree::GitLock;
+use crate::package::tree::{GitFetchOptions, GitLock};
 use std::io;
 
 pub mod args;
@@ -17,6 +17,13 @@ pub enum WhichError {
 }
 #[derive(Debug)]
 pub struct GitCloneError(pub String); // TODO: create a more domain-specific error time like the actual git2::Error
+#[derive(Debug)]
+pub enum DownloadError {
+    /// The server has no file at the url
+    NotFound(String),
+    /// The download failed for another reason, like a network error
+    Failed(String),
+}
 
 impl std::fmt::Display for WhichError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -36,10 +43,22 @@ impl std::fmt::Display for GitCloneError {
     }
 }
 
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::NotFound(message) | DownloadError::Failed(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
 impl std::error::Error for WhichError {}
 
 impl std::error::Error for GitCloneError {}
 
+impl std::error::Error for DownloadError {}
+
 pub struct GitUrl<'a>(&'a str);
 
 impl<'a> From<&'a str> for GitUrl<'a> {
@@ -59,5 +78,15 @@ pub type GitCloneCapability<'a> =
     Box<dyn Fn(GitUrl, &std::path::Path) -> Result<(), GitCloneError> + 'a>;
 pub type FsReadCapability<'a> = Box<dyn Fn(&std::path::Path) -> io::Result<String> + 'a>;
 pub type GitCloneAndCheckoutCap<'a> = Box<
-    dyn Fn(GitUrl, &std::path::Path, Option<GitLock>) -> Result<Option<String>, GitCloneError> + 'a,
+    dyn Fn(
+            GitUrl,
+            &std::path::Path,
+            Option<GitLock>,
+            GitFetchOptions,
+            Option<&std::path::Path>,
+        ) -> Result<Option<String>, GitCloneError>
+        + 'a,
 >;
+pub type DownloadCapability<'a> = Box<dyn Fn(&url::Url) -> Result<Vec<u8>, DownloadError> + 'a>;
+/// lists the tags of a repository, offline only the tags of the cached mirror are listed
+pub type GitListTagsCap<'a> = Box<dyn Fn(GitUrl, bool) -> Result<Vec<String>, GitCloneError> + 'a>;
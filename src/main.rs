@@ -1,19 +1,35 @@
-use liblingo::args::TargetLanguage;
+use liblingo::args::{BuildSystem, TargetLanguage};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::error::Error;
 use std::io::ErrorKind;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitCode};
+use std::sync::Mutex;
+use std::time::SystemTime;
 use std::{env, io};
+use url::Url;
 
 use clap::Parser;
-use git2::BranchType::{Local, Remote};
-use git2::{BranchType, Object, ObjectType, Reference, Repository};
-use liblingo::args::InitArgs;
+use git2::{
+    Commit, Cred, CredentialType, Direction, ErrorClass, ErrorCode, FetchOptions, ObjectType,
+    RemoteCallbacks, Repository, Tree,
+};
 use liblingo::args::{BuildArgs, Command as ConsoleCommand, CommandLineArgs};
+use liblingo::args::{DependencyArgs, HashArgs, InitArgs, SbomArgs, SbomFormat, VerifyArgs};
 use liblingo::backends::{BatchBuildResults, BuildCommandOptions, CommandSpec};
-use liblingo::package::tree::GitLock;
-use liblingo::package::{Config, ConfigFile};
+use liblingo::package::features::FeatureRequest;
+use liblingo::package::management::DependencyManager;
+use liblingo::package::tree::{GitFetchOptions, GitLock};
+use liblingo::package::{license, sbom, App, Config, ConfigFile, Toolchain, OUTPUT_DIRECTORY};
 use liblingo::util::errors::{BuildResult, LingoError};
-use liblingo::{GitCloneAndCheckoutCap, GitCloneError, GitUrl, WhichCapability, WhichError};
+use liblingo::util::sha1dir;
+use liblingo::{
+    DownloadCapability, DownloadError, GitCloneAndCheckoutCap, GitCloneError, GitListTagsCap,
+    GitUrl, WhichCapability, WhichError,
+};
+use sha1::{Digest, Sha1};
 
 fn do_which(cmd: &str) -> Result<PathBuf, WhichError> {
     which::which(cmd).map_err(|err| match err {
@@ -25,73 +41,503 @@ fn do_which(cmd: &str) -> Result<PathBuf, WhichError> {
     })
 }
 
-fn get_branch<'a>(
-    repo: &'a git2::Repository,
-    branch: &str,
-    branch_type: BranchType,
-) -> Result<(Object<'a>, Option<Reference<'a>>), GitCloneError> {
-    let reference = repo
-        .find_branch(branch, branch_type)
-        .map_err(|_| GitCloneError("cannot find branch".to_string()))?
-        .into_reference();
-    Ok((
-        reference
-            .peel(ObjectType::Any)
-            .map_err(|_| GitCloneError("cannot peel object".to_string()))?,
-        Some(reference),
+/// directory holding the bare mirrors of git dependencies
+fn git_cache_directory() -> PathBuf {
+    if let Some(dir) = env::var_os("LINGO_CACHE_DIR") {
+        return PathBuf::from(dir).join("git");
+    }
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("lingo")
+        .join("git")
+}
+
+/// every repository gets its own mirror, named after the repository and a hash of its url
+fn mirror_path(url: &str) -> PathBuf {
+    let name = url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or("repository");
+    let hash: String = Sha1::digest(url.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    git_cache_directory().join(format!("{name}-{hash}"))
+}
+
+/// Environment variable holding a token used for https git remotes
+const GIT_TOKEN_VARIABLE: &str = "LINGO_GIT_TOKEN";
+/// Environment variable pointing to the private key used for ssh git remotes
+const SSH_KEY_VARIABLE: &str = "LINGO_SSH_KEY";
+/// Environment variable holding the passphrase of the ssh key
+const SSH_PASSPHRASE_VARIABLE: &str = "LINGO_SSH_KEY_PASSPHRASE";
+
+/// the key from LINGO_SSH_KEY or the default keys inside ~/.ssh
+fn ssh_key_files() -> Vec<PathBuf> {
+    if let Some(key) = env::var_os(SSH_KEY_VARIABLE) {
+        return vec![PathBuf::from(key)];
+    }
+    let Some(home) = env::var_os("HOME") else {
+        return vec![];
+    };
+    ["id_ed25519", "id_ecdsa", "id_rsa"]
+        .iter()
+        .map(|key| PathBuf::from(&home).join(".ssh").join(key))
+        .filter(|key| key.exists())
+        .collect()
+}
+
+/// A way to authenticate against a git remote
+#[derive(Debug, PartialEq)]
+enum Credential {
+    SshAgent,
+    SshKey(PathBuf),
+    Token(String),
+    Helper,
+}
+
+/// Offers every credential once, in the order ssh-agent and key files for ssh remotes, the
+/// token from LINGO_GIT_TOKEN and the git credential helpers for https remotes
+struct CredentialChoice {
+    tried_agent: bool,
+    key_files: std::vec::IntoIter<PathBuf>,
+    token: Option<String>,
+    tried_helper: bool,
+}
+
+impl CredentialChoice {
+    fn new(key_files: Vec<PathBuf>, token: Option<String>) -> Self {
+        CredentialChoice {
+            tried_agent: false,
+            key_files: key_files.into_iter(),
+            token,
+            tried_helper: false,
+        }
+    }
+
+    /// the next credential of a type the remote accepts
+    fn next(&mut self, allowed: CredentialType) -> Option<Credential> {
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !self.tried_agent {
+                self.tried_agent = true;
+                return Some(Credential::SshAgent);
+            }
+            if let Some(key) = self.key_files.next() {
+                return Some(Credential::SshKey(key));
+            }
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some(token) = self.token.take() {
+                return Some(Credential::Token(token));
+            }
+            if !self.tried_helper {
+                self.tried_helper = true;
+                return Some(Credential::Helper);
+            }
+        }
+
+        None
+    }
+}
+
+/// Provides credentials for private repositories, see [`CredentialChoice`] for the order. The
+/// methods that were tried are recorded in `attempts`.
+fn remote_callbacks(attempts: &RefCell<Vec<String>>) -> RemoteCallbacks<'_> {
+    let mut choice = CredentialChoice::new(ssh_key_files(), env::var(GIT_TOKEN_VARIABLE).ok());
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username_from_url, allowed| {
+        let username = username_from_url.unwrap_or("git");
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+
+        match choice.next(allowed) {
+            Some(Credential::SshAgent) => {
+                attempts.borrow_mut().push("ssh-agent".to_string());
+                Cred::ssh_key_from_agent(username)
+            }
+            Some(Credential::SshKey(key)) => {
+                attempts
+                    .borrow_mut()
+                    .push(format!("ssh key {}", key.display()));
+                let passphrase = env::var(SSH_PASSPHRASE_VARIABLE).ok();
+                Cred::ssh_key(username, None, &key, passphrase.as_deref())
+            }
+            Some(Credential::Token(token)) => {
+                attempts.borrow_mut().push(GIT_TOKEN_VARIABLE.to_string());
+                let username = username_from_url.unwrap_or("x-access-token");
+                Cred::userpass_plaintext(username, &token)
+            }
+            Some(Credential::Helper) => {
+                attempts
+                    .borrow_mut()
+                    .push("git credential helper".to_string());
+                let config = git2::Config::open_default()?;
+                Cred::credential_helper(&config, url, username_from_url)
+            }
+            None => Err(git2::Error::from_str("no more credentials to try")),
+        }
+    });
+    callbacks
+}
+
+/// explains which credentials were tried if the remote rejected them
+fn remote_error(url: &str, error: git2::Error, attempts: &RefCell<Vec<String>>) -> GitCloneError {
+    let attempts = attempts.borrow();
+    let authentication_failed = error.code() == ErrorCode::Auth
+        || error.class() == ErrorClass::Callback
+        || (error.class() == ErrorClass::Ssh && !attempts.is_empty());
+    if !authentication_failed {
+        return GitCloneError(format!("cannot fetch {url} {}", error.message()));
+    }
+
+    let tried = if attempts.is_empty() {
+        "no credentials were available".to_string()
+    } else {
+        format!("tried {}", attempts.join(", "))
+    };
+    GitCloneError(format!(
+        "authentication failed for {url} ({tried}): {}. For ssh remotes add your key to the ssh-agent or set {SSH_KEY_VARIABLE}, for https remotes set {GIT_TOKEN_VARIABLE} or configure a git credential helper",
+        error.message()
     ))
 }
 
+/// error for repositories or revisions that are needed offline but not mirrored
+fn not_cached(url: &str, what: &str) -> GitCloneError {
+    GitCloneError(format!(
+        "{what} of {url} is not in the cache at {}, it has to be fetched once without --offline",
+        git_cache_directory().display()
+    ))
+}
+
+/// Waits until no other lingo process updates the mirror. The mirror stays locked until the
+/// returned file is dropped.
+fn lock_mirror(path: &Path) -> Result<std::fs::File, GitCloneError> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let lock_path = path.with_file_name(format!("{name}.lock"));
+    let lock = std::fs::create_dir_all(git_cache_directory())
+        .and_then(|_| std::fs::File::create(&lock_path))
+        .and_then(|file| file.lock().map(|_| file))
+        .map_err(|e| GitCloneError(format!("cannot lock {} {e}", lock_path.display())))?;
+    Ok(lock)
+}
+
+/// Opens the bare mirror of the repository and fetches what is needed to check out the
+/// requested revision. Tags and commits that are already mirrored are not fetched again.
+/// Offline the mirror is used as it is.
+fn update_mirror(
+    url: &str,
+    git_lock: Option<&GitLock>,
+    options: GitFetchOptions,
+) -> Result<Repository, GitCloneError> {
+    let path = mirror_path(url);
+    if options.offline {
+        return Repository::open_bare(&path).map_err(|_| not_cached(url, "the repository"));
+    }
+    let _lock = lock_mirror(&path)?;
+    let repo = match Repository::open_bare(&path) {
+        Ok(repo) => repo,
+        Err(_) => Repository::init_bare(&path)
+            .map_err(|e| GitCloneError(format!("cannot create mirror {e}")))?,
+    };
+
+    let mirrored = match git_lock {
+        Some(GitLock::Tag(tag)) => repo.revparse_single(&format!("refs/tags/{tag}")).is_ok(),
+        Some(GitLock::Rev(rev)) => repo
+            .revparse_single(rev)
+            .and_then(|object| object.peel_to_commit())
+            .is_ok(),
+        _ => false,
+    };
+    if mirrored {
+        return Ok(repo);
+    }
+
+    // dependencies from the same repository share the mirror, which is fetched once per run
+    static UPDATED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+    let updated = UPDATED.lock().map(|updated| updated.contains(url));
+    if updated.unwrap_or(false) && find_commit(&repo, git_lock).is_ok() {
+        return Ok(repo);
+    }
+
+    {
+        let mut remote = repo
+            .remote_anonymous(url)
+            .map_err(|e| GitCloneError(format!("invalid remote {e}")))?;
+        let attempts = RefCell::new(Vec::new());
+        let default_branch = remote
+            .connect_auth(Direction::Fetch, Some(remote_callbacks(&attempts)), None)
+            .map_err(|e| remote_error(url, e, &attempts))?
+            .default_branch()
+            .ok()
+            .and_then(|branch| branch.as_str().map(String::from));
+
+        // commits can only be fetched through the refs containing them
+        let single = (options.single_branch || options.shallow)
+            && !matches!(git_lock, Some(GitLock::Rev(_)));
+        let refspecs = match (git_lock, &default_branch) {
+            (Some(GitLock::Branch(branch)), _) if single => {
+                vec![format!("+refs/heads/{branch}:refs/heads/{branch}")]
+            }
+            (Some(GitLock::Tag(tag)), _) if single => {
+                vec![format!("+refs/tags/{tag}:refs/tags/{tag}")]
+            }
+            (None, Some(default_branch)) if single => {
+                vec![format!("+{default_branch}:{default_branch}")]
+            }
+            _ => vec![
+                "+refs/heads/*:refs/heads/*".to_string(),
+                "+refs/tags/*:refs/tags/*".to_string(),
+            ],
+        };
+
+        // local repositories are cheap to fetch and libgit2 can't fetch them shallowly
+        let local = url.starts_with("file://") || Path::new(url).exists();
+        let attempts = RefCell::new(Vec::new());
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(remote_callbacks(&attempts));
+        if single && options.shallow && !local {
+            fetch_options.depth(1);
+        } else if repo.is_shallow() {
+            // a previous shallow fetch left the mirror without the full history
+            fetch_options.depth(i32::MAX);
+        }
+
+        remote
+            .fetch(&refspecs, Some(&mut fetch_options), None)
+            .map_err(|e| remote_error(url, e, &attempts))?;
+
+        if let Some(default_branch) = default_branch {
+            repo.set_head(&default_branch)
+                .map_err(|e| GitCloneError(format!("cannot update mirror {e}")))?;
+        }
+        if let Ok(mut updated) = UPDATED.lock() {
+            updated.insert(url.to_string());
+        }
+    }
+
+    Ok(repo)
+}
+
+/// writes the files of the tree to the given directory, submodules become empty directories
+fn export_tree(repo: &Repository, tree: &Tree, outpath: &Path) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(outpath)?;
+    for entry in tree.iter() {
+        let path = outpath.join(entry.name().ok_or("invalid file name")?);
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                export_tree(repo, &repo.find_tree(entry.id())?, &path)?;
+            }
+            Some(ObjectType::Blob) => {
+                let blob = repo.find_blob(entry.id())?;
+                let _ = std::fs::remove_file(&path);
+                #[cfg(unix)]
+                if entry.filemode() == 0o120000 {
+                    let target = std::str::from_utf8(blob.content())?;
+                    std::os::unix::fs::symlink(target, &path)?;
+                    continue;
+                }
+                std::fs::write(&path, blob.content())?;
+                #[cfg(unix)]
+                if entry.filemode() == 0o100755 {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+                }
+            }
+            Some(ObjectType::Commit) => std::fs::create_dir_all(&path)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// resolves the requested revision inside the mirror
+fn find_commit<'a>(
+    repo: &'a Repository,
+    git_lock: Option<&GitLock>,
+) -> Result<Commit<'a>, GitCloneError> {
+    let spec = match git_lock {
+        Some(GitLock::Tag(tag)) => format!("refs/tags/{tag}"),
+        Some(GitLock::Branch(branch)) => format!("refs/heads/{branch}"),
+        Some(GitLock::Rev(rev)) => rev.clone(),
+        None => "HEAD".to_string(),
+    };
+    repo.revparse_single(&spec)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|e| GitCloneError(format!("cannot find {spec} {e}")))
+}
+
+/// checks out the submodules of the commit that lie inside `subdir`, using a mirror for each
+/// of them
+fn checkout_submodules(
+    repo: &Repository,
+    url: &str,
+    commit: &Commit,
+    subdir: &Path,
+    outpath: &Path,
+    options: GitFetchOptions,
+) -> Result<(), GitCloneError> {
+    let tree = commit
+        .tree()
+        .map_err(|e| GitCloneError(format!("cannot read tree {e}")))?;
+    let Ok(gitmodules) = tree.get_path(Path::new(".gitmodules")) else {
+        return Ok(());
+    };
+
+    // the .gitmodules file of the commit may lie outside of the checked out subdirectory
+    let read_gitmodules = || -> Result<git2::Config, Box<dyn Error>> {
+        let blob = repo.find_blob(gitmodules.id())?;
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(blob.content())?;
+        Ok(git2::Config::open(file.path())?.snapshot()?)
+    };
+    let config =
+        read_gitmodules().map_err(|e| GitCloneError(format!("cannot read .gitmodules {e}")))?;
+
+    let mut submodules = Vec::new();
+    let mut entries = config
+        .entries(Some(r"submodule\..*\.path"))
+        .map_err(|e| GitCloneError(format!("cannot read .gitmodules {e}")))?;
+    while let Some(Ok(entry)) = entries.next() {
+        if let (Some(name), Some(path)) = (entry.name(), entry.value()) {
+            let key = name.trim_end_matches(".path");
+            if let Ok(submodule_url) = config.get_string(&format!("{key}.url")) {
+                submodules.push((PathBuf::from(path), submodule_url));
+            }
+        }
+    }
+
+    for (path, submodule_url) in submodules {
+        let Ok(relative) = path.strip_prefix(subdir) else {
+            continue;
+        };
+        // relative urls are resolved against the url of the parent repository
+        let submodule_url = if submodule_url.starts_with("./") || submodule_url.starts_with("../") {
+            Url::parse(&format!("{}/", url.trim_end_matches('/')))
+                .and_then(|base| base.join(&submodule_url))
+                .map(String::from)
+                .map_err(|e| GitCloneError(format!("invalid submodule url {e}")))?
+        } else {
+            submodule_url
+        };
+        let Ok(entry) = tree.get_path(&path) else {
+            continue;
+        };
+        do_clone_and_checkout(
+            GitUrl::from(submodule_url.as_str()),
+            &outpath.join(relative),
+            Some(GitLock::Rev(entry.id().to_string())),
+            options,
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Checks out the revision from a cached bare mirror of the repository, the checkout
+/// itself contains no git metadata. If a subdirectory is given only its contents are
+/// checked out. Returns the commit that was checked out.
 fn do_clone_and_checkout(
     git_url: GitUrl,
     outpath: &Path,
     git_tag: Option<GitLock>,
+    options: GitFetchOptions,
+    subdir: Option<&Path>,
 ) -> Result<Option<String>, GitCloneError> {
-    let repo = Repository::clone_recurse(<&str>::from(git_url), outpath)
-        .map_err(|_| GitCloneError("clone failed".to_string()))?;
-    let mut git_rev = None;
-
-    if let Some(git_lock) = git_tag {
-        let (object, reference) = match git_lock {
-            GitLock::Tag(tag) => repo
-                .revparse_ext(&tag)
-                .map_err(|e| GitCloneError(format!("cannot parse rev {e}")))?,
-            GitLock::Branch(branch) => {
-                if let Ok(val) = get_branch(&repo, &branch, Local) {
-                    val
-                } else {
-                    get_branch(&repo, &branch, Remote)?
-                }
-            }
-            GitLock::Rev(rev) => repo
-                .revparse_ext(&rev)
-                .map_err(|e| GitCloneError(format!("cannot parse rev {e}")))?,
-        };
-        repo.checkout_tree(&object, None)
-            .map_err(|e| GitCloneError(format!("cannot checkout rev {e}")))?;
+    let url = <&str>::from(git_url);
+    let repo = update_mirror(url, git_tag.as_ref(), options)?;
+    let commit = find_commit(&repo, git_tag.as_ref()).map_err(|e| {
+        if options.offline {
+            not_cached(url, "the requested revision")
+        } else {
+            e
+        }
+    })?;
 
-        // TODO: this produces hard to debug output
+    std::fs::create_dir_all(outpath)
+        .map_err(|e| GitCloneError(format!("cannot create {} {e}", outpath.display())))?;
+    let tree = commit
+        .tree()
+        .map_err(|e| GitCloneError(format!("cannot read tree {e}")))?;
+    let subdir = subdir.unwrap_or(Path::new(""));
+    let subtree = if subdir.as_os_str().is_empty() {
+        tree
+    } else {
+        tree.get_path(subdir)
+            .and_then(|entry| repo.find_tree(entry.id()))
+            .map_err(|_| {
+                GitCloneError(format!(
+                    "{} does not exist in {url} at {}",
+                    subdir.display(),
+                    commit.id()
+                ))
+            })?
+    };
+    export_tree(&repo, &subtree, outpath)
+        .map_err(|e| GitCloneError(format!("cannot checkout rev {e}")))?;
 
-        match reference {
-            // gref is an actual reference like branches or tags
-            Some(gref) => {
-                git_rev = gref.target().map(|v| v.to_string());
-                repo.set_head(gref.name().unwrap())
-            }
-            // this is a commit, not a reference
-            None => repo.set_head_detached(object.id()),
-        }
-        .map_err(|_| GitCloneError("cannot checkout rev".to_string()))?;
+    checkout_submodules(&repo, url, &commit, subdir, outpath, options)?;
+
+    Ok(Some(commit.id().to_string()))
+}
+
+fn do_list_tags(git_url: GitUrl, offline: bool) -> Result<Vec<String>, GitCloneError> {
+    let url = <&str>::from(git_url);
+    if offline {
+        let repo = Repository::open_bare(mirror_path(url))
+            .map_err(|_| not_cached(url, "the repository"))?;
+        let tags = repo
+            .tag_names(None)
+            .map_err(|e| GitCloneError(format!("cannot list the tags of the mirror {e}")))?;
+        return Ok(tags.iter().flatten().map(String::from).collect());
     }
 
-    Ok(git_rev)
+    let mut remote = git2::Remote::create_detached(url)
+        .map_err(|e| GitCloneError(format!("invalid remote {e}")))?;
+    let attempts = RefCell::new(Vec::new());
+    let connection = remote
+        .connect_auth(Direction::Fetch, Some(remote_callbacks(&attempts)), None)
+        .map_err(|e| remote_error(url, e, &attempts))?;
+
+    let tags = connection
+        .list()
+        .map_err(|e| GitCloneError(format!("cannot list remote references {e}")))?
+        .iter()
+        .filter_map(|head| head.name().strip_prefix("refs/tags/"))
+        // peeled annotated tags are listed a second time
+        .filter(|tag| !tag.ends_with("^{}"))
+        .map(String::from)
+        .collect();
+    Ok(tags)
+}
+
+fn do_download(url: &Url) -> Result<Vec<u8>, DownloadError> {
+    let response = ureq::get(url.as_str()).call().map_err(|e| match e {
+        ureq::Error::Status(404, _) => DownloadError::NotFound(format!("{url} does not exist")),
+        e => DownloadError::Failed(format!("cannot download {url}: {e}")),
+    })?;
+
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .map_err(|e| DownloadError::Failed(format!("cannot download {url}: {e}")))?;
+    Ok(bytes)
 }
 
 fn do_read_to_string(p: &Path) -> io::Result<String> {
     std::fs::read_to_string(p)
 }
 
-fn main() {
+fn main() -> ExitCode {
     print_logger::new().init().unwrap();
     // parses command line arguments
     let args = CommandLineArgs::parse();
@@ -103,14 +549,16 @@ fn main() {
     // tries to read Lingo.toml
     let mut wrapped_config = lingo_path.as_ref().and_then(|path| {
         ConfigFile::from(path, Box::new(do_read_to_string))
+            .map_err(|err| log::error!("Error while reading Lingo.toml: {}", err))
+            .ok()?
+            .to_config(path.parent().unwrap())
             .map_err(|err| log::error!("Error while reading Lingo.toml: {}", err))
             .ok()
-            .map(|cf| cf.to_config(path.parent().unwrap()))
     });
 
     let result: BuildResult = validate(&mut wrapped_config, &args.command);
     if result.is_err() {
-        print_res(result)
+        return print_res(result);
     }
 
     let result = execute_command(
@@ -120,17 +568,26 @@ fn main() {
         Box::new(do_clone_and_checkout),
     );
 
+    // CI relies on the exit code to notice failed builds, lock checks and license policies
     match result {
-        CommandResult::Batch(res) => res.print_results(),
+        CommandResult::Batch(res) => {
+            res.print_results();
+            if res.is_err() {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
         CommandResult::Single(res) => print_res(res),
     }
 }
 
-fn print_res(result: BuildResult) {
+fn print_res(result: BuildResult) -> ExitCode {
     match result {
-        Ok(_) => {}
+        Ok(_) => ExitCode::SUCCESS,
         Err(errs) => {
             log::error!("{}", errs);
+            ExitCode::FAILURE
         }
     }
 }
@@ -152,12 +609,27 @@ fn validate(config: &mut Option<Config>, command: &ConsoleCommand) -> BuildResul
             if !build.apps.is_empty() {
                 config.apps.retain(|app| build.apps.contains(&app.name));
             }
+            if let Some(file) = &build.toolchain {
+                use_toolchain_file(&mut config.apps, &env::current_dir()?.join(file));
+            }
             Ok(())
         }
         _ => Ok(()),
     }
 }
 
+/// The toolchain file of the CLI replaces the file of the apps built with CMake. These are the
+/// C, C++ and uC apps, uC apps are built by their own CMake backend.
+fn use_toolchain_file(apps: &mut [App], file: &Path) {
+    let which: WhichCapability = Box::new(do_which);
+    for app in apps {
+        if app.build_system(&which) == BuildSystem::CMake {
+            let toolchain = app.toolchain.clone().unwrap_or_default();
+            app.use_toolchain(toolchain.merge(Toolchain::from_file(file.to_path_buf())));
+        }
+    }
+}
+
 fn execute_command<'a>(
     config: &'a mut Option<Config>,
     command: ConsoleCommand,
@@ -168,6 +640,7 @@ fn execute_command<'a>(
         (_, ConsoleCommand::Init(init_config)) => {
             CommandResult::Single(do_init(init_config, &git_clone_capability))
         }
+        (_, ConsoleCommand::Hash(hash_args)) => CommandResult::Single(hash(&hash_args)),
         (None, _) => CommandResult::Single(Err(Box::new(io::Error::new(
             ErrorKind::NotFound,
             "Error: Missing Lingo.toml file",
@@ -187,6 +660,16 @@ fn execute_command<'a>(
         (Some(config), ConsoleCommand::Clean) => {
             CommandResult::Batch(run_command(CommandSpec::Clean, config, true))
         }
+        (Some(_), ConsoleCommand::Verify(verify_args)) => {
+            CommandResult::Single(verify(&verify_args, &git_clone_capability))
+        }
+        (Some(config), ConsoleCommand::Outdated) => CommandResult::Single(outdated(config)),
+        (Some(config), ConsoleCommand::Licenses(dependency_args)) => {
+            CommandResult::Single(licenses(&dependency_args, config, &git_clone_capability))
+        }
+        (Some(config), ConsoleCommand::Sbom(sbom_args)) => {
+            CommandResult::Single(sbom(&sbom_args, config, &git_clone_capability))
+        }
         _ => todo!(),
     }
 }
@@ -201,6 +684,107 @@ fn do_init(init_config: InitArgs, git_clone_capability: &GitCloneAndCheckoutCap)
     )
 }
 
+fn outdated(config: &Config) -> BuildResult {
+    Ok(DependencyManager::outdated(
+        &config.active_dependencies(&FeatureRequest::default_features())?,
+        &PathBuf::from(OUTPUT_DIRECTORY),
+        &(Box::new(do_list_tags) as GitListTagsCap),
+        &(Box::new(do_download) as DownloadCapability),
+    )?)
+}
+
+fn licenses(
+    args: &DependencyArgs,
+    config: &Config,
+    git_clone_capability: &GitCloneAndCheckoutCap,
+) -> BuildResult {
+    let manager = DependencyManager::from_dependencies(
+        config.active_dependencies(&args.feature_request())?,
+        &PathBuf::from(OUTPUT_DIRECTORY),
+        args.resolution_options(),
+        config.registry.as_ref(),
+        &config.patches,
+        git_clone_capability,
+        &(Box::new(do_download) as DownloadCapability),
+        &(Box::new(do_list_tags) as GitListTagsCap),
+    )?;
+
+    let licenses = manager.licenses();
+    license::print(&licenses);
+    Ok(license::check(&licenses, &config.policy)?)
+}
+
+fn sbom(
+    args: &SbomArgs,
+    config: &Config,
+    git_clone_capability: &GitCloneAndCheckoutCap,
+) -> BuildResult {
+    let dependencies = config.active_dependencies(&args.dependencies.feature_request())?;
+    let manager = DependencyManager::from_dependencies(
+        dependencies.clone(),
+        &PathBuf::from(OUTPUT_DIRECTORY),
+        args.dependencies.resolution_options(),
+        config.registry.as_ref(),
+        &config.patches,
+        git_clone_capability,
+        &(Box::new(do_download) as DownloadCapability),
+        &(Box::new(do_list_tags) as GitListTagsCap),
+    )?;
+
+    let lfc = args.lfc.clone().or_else(|| do_which("lfc").ok());
+    let which = Box::new(do_which) as WhichCapability;
+    let apps: Vec<_> = config
+        .apps
+        .iter()
+        .map(|app| sbom::describe_app(app, lfc.as_deref(), &which))
+        .collect();
+    let direct: Vec<String> = dependencies.into_iter().map(|(name, _)| name).collect();
+    let components = manager.components();
+
+    let document = match args.format {
+        SbomFormat::CycloneDx => sbom::cyclonedx(
+            &config.package,
+            &direct,
+            &components,
+            &apps,
+            SystemTime::now(),
+        ),
+        SbomFormat::Spdx => sbom::spdx(
+            &config.package,
+            &direct,
+            &components,
+            &apps,
+            SystemTime::now(),
+        ),
+    };
+    let document = serde_json::to_string_pretty(&document)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, document + "\n")?,
+        None => println!("{document}"),
+    }
+    Ok(())
+}
+
+fn hash(args: &HashArgs) -> BuildResult {
+    if !args.path.is_dir() {
+        return Err(Box::new(io::Error::new(
+            ErrorKind::NotFound,
+            format!("{} is not a directory", args.path.display()),
+        )));
+    }
+    println!("{}", sha1dir::checksum_dir(&args.path, false)?);
+    Ok(())
+}
+
+fn verify(args: &VerifyArgs, git_clone_capability: &GitCloneAndCheckoutCap) -> BuildResult {
+    Ok(DependencyManager::verify(
+        &PathBuf::from(OUTPUT_DIRECTORY),
+        args.repair,
+        git_clone_capability,
+        &(Box::new(do_download) as DownloadCapability),
+    )?)
+}
+
 fn build<'a>(args: &BuildArgs, config: &'a mut Config) -> BatchBuildResults<'a> {
     run_command(
         CommandSpec::Build(BuildCommandOptions {
@@ -210,19 +794,27 @@ fn build<'a>(args: &BuildArgs, config: &'a mut Config) -> BatchBuildResults<'a>
                 .expect("TODO replace me"),
             max_threads: args.threads,
             keep_going: args.keep_going,
+            resolution: args.dependencies.resolution_options(),
+            features: args.dependencies.feature_request(),
         }),
         config,
         args.keep_going,
     )
 }
 
-fn run_command(task: CommandSpec, config: &mut Config, _fail_at_end: bool) -> BatchBuildResults {
+fn run_command(
+    task: CommandSpec,
+    config: &mut Config,
+    _fail_at_end: bool,
+) -> BatchBuildResults<'_> {
     let _apps = config.apps.iter().collect::<Vec<_>>();
     liblingo::backends::execute_command(
         &task,
         config,
         Box::new(do_which),
         Box::new(do_clone_and_checkout),
+        Box::new(do_download),
+        Box::new(do_list_tags),
     )
 }
 
@@ -230,3 +822,232 @@ enum CommandResult<'a> {
     Batch(BatchBuildResults<'a>),
     Single(BuildResult),
 }
+
+/// the tests share one cache directory, every test mirrors repositories of its own
+#[cfg(test)]
+fn use_test_cache() {
+    static CACHE: std::sync::OnceLock<tempfile::TempDir> = std::sync::OnceLock::new();
+    CACHE.get_or_init(|| {
+        let cache = tempfile::tempdir().unwrap();
+        env::set_var("LINGO_CACHE_DIR", cache.path());
+        cache
+    });
+}
+
+/// commits every file of the working directory
+#[cfg(test)]
+fn commit_all(repo: &Repository, message: &str) -> git2::Oid {
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("lingo", "lingo@example.com").unwrap();
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<&Commit> = parent.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_offline_checkout_from_mirror() {
+    use_test_cache();
+    let source = tempfile::tempdir().unwrap();
+    let repo = Repository::init(source.path()).unwrap();
+    std::fs::write(source.path().join("Lingo.toml"), "[package]").unwrap();
+    let commit = commit_all(&repo, "initial");
+    repo.tag_lightweight("v0.1.0", &repo.find_object(commit, None).unwrap(), false)
+        .unwrap();
+    let url = Url::from_directory_path(source.path()).unwrap().to_string();
+    let url = url.as_str();
+    let checkout = tempfile::tempdir().unwrap();
+    let offline = GitFetchOptions {
+        offline: true,
+        ..Default::default()
+    };
+
+    // nothing is mirrored yet
+    assert!(do_list_tags(GitUrl::from(url), true).is_err());
+    assert!(
+        do_clone_and_checkout(GitUrl::from(url), checkout.path(), None, offline, None).is_err()
+    );
+
+    do_clone_and_checkout(
+        GitUrl::from(url),
+        &checkout.path().join("online"),
+        None,
+        GitFetchOptions::default(),
+        None,
+    )
+    .unwrap();
+
+    // without the source every fetch fails
+    drop(repo);
+    source.close().unwrap();
+
+    assert_eq!(do_list_tags(GitUrl::from(url), true).unwrap(), ["v0.1.0"]);
+    let rev = do_clone_and_checkout(
+        GitUrl::from(url),
+        &checkout.path().join("offline"),
+        Some(GitLock::Tag("v0.1.0".to_string())),
+        offline,
+        None,
+    )
+    .unwrap();
+    assert_eq!(rev, Some(commit.to_string()));
+    assert!(checkout.path().join("offline/Lingo.toml").exists());
+
+    let error = do_clone_and_checkout(
+        GitUrl::from(url),
+        &checkout.path().join("missing"),
+        Some(GitLock::Tag("v0.2.0".to_string())),
+        offline,
+        None,
+    )
+    .unwrap_err();
+    assert!(error.0.contains("not in the cache"));
+}
+
+#[test]
+fn test_mirror_is_fetched_once_per_run() {
+    use_test_cache();
+    let source = tempfile::tempdir().unwrap();
+    let repo = Repository::init(source.path()).unwrap();
+    std::fs::write(source.path().join("Lingo.toml"), "[package]").unwrap();
+    let first = commit_all(&repo, "first");
+    let url = Url::from_directory_path(source.path()).unwrap().to_string();
+    let url = url.as_str();
+    let checkout = tempfile::tempdir().unwrap();
+    let clone = |name: &str, git_lock: Option<GitLock>| {
+        do_clone_and_checkout(
+            GitUrl::from(url),
+            &checkout.path().join(name),
+            git_lock,
+            GitFetchOptions::default(),
+            None,
+        )
+    };
+
+    assert_eq!(clone("first", None).unwrap(), Some(first.to_string()));
+    let mirror = mirror_path(url);
+    assert!(mirror
+        .with_file_name(format!(
+            "{}.lock",
+            mirror.file_name().unwrap().to_string_lossy()
+        ))
+        .exists());
+
+    // the default branch was already fetched during this run
+    std::fs::write(source.path().join("Lib.lf"), "target C").unwrap();
+    commit_all(&repo, "second");
+    assert_eq!(clone("second", None).unwrap(), Some(first.to_string()));
+
+    // mirrored commits don't need the source
+    drop(repo);
+    source.close().unwrap();
+    let rev = clone("rev", Some(GitLock::Rev(first.to_string()))).unwrap();
+    assert_eq!(rev, Some(first.to_string()));
+    assert!(checkout.path().join("rev/Lingo.toml").exists());
+}
+
+#[test]
+fn test_credential_order() {
+    let keys = vec![PathBuf::from("id_ed25519"), PathBuf::from("id_rsa")];
+    let any = CredentialType::SSH_KEY | CredentialType::USER_PASS_PLAINTEXT;
+
+    let mut choice = CredentialChoice::new(keys.clone(), Some("secret".to_string()));
+    let offered: Vec<Credential> = std::iter::from_fn(|| choice.next(any)).collect();
+    assert_eq!(
+        offered,
+        [
+            Credential::SshAgent,
+            Credential::SshKey(keys[0].clone()),
+            Credential::SshKey(keys[1].clone()),
+            Credential::Token("secret".to_string()),
+            Credential::Helper,
+        ]
+    );
+
+    // https remotes don't accept ssh keys, without a token only the helper is left
+    let mut choice = CredentialChoice::new(keys.clone(), None);
+    assert_eq!(
+        choice.next(CredentialType::USER_PASS_PLAINTEXT),
+        Some(Credential::Helper)
+    );
+    assert_eq!(choice.next(CredentialType::USER_PASS_PLAINTEXT), None);
+
+    // ssh remotes never fall back to the token
+    let mut choice = CredentialChoice::new(vec![], Some("secret".to_string()));
+    assert_eq!(
+        choice.next(CredentialType::SSH_KEY),
+        Some(Credential::SshAgent)
+    );
+    assert_eq!(choice.next(CredentialType::SSH_KEY), None);
+}
+
+#[test]
+fn test_checkout_subdirectory() {
+    use_test_cache();
+    let source = tempfile::tempdir().unwrap();
+    let repo = Repository::init(source.path()).unwrap();
+    std::fs::create_dir_all(source.path().join("packages/alpha/src")).unwrap();
+    std::fs::write(source.path().join("README.md"), "monorepo").unwrap();
+    std::fs::write(source.path().join("packages/alpha/Lingo.toml"), "[package]").unwrap();
+    std::fs::write(source.path().join("packages/alpha/src/Lib.lf"), "target C").unwrap();
+    let commit = commit_all(&repo, "initial");
+    let url = Url::from_directory_path(source.path()).unwrap().to_string();
+    let checkout = tempfile::tempdir().unwrap();
+
+    let rev = do_clone_and_checkout(
+        GitUrl::from(url.as_str()),
+        &checkout.path().join("alpha"),
+        None,
+        GitFetchOptions::default(),
+        Some(Path::new("packages/alpha")),
+    )
+    .unwrap();
+    assert_eq!(rev, Some(commit.to_string()));
+    assert!(checkout.path().join("alpha/Lingo.toml").exists());
+    assert!(checkout.path().join("alpha/src/Lib.lf").exists());
+    assert!(!checkout.path().join("alpha/README.md").exists());
+
+    let error = do_clone_and_checkout(
+        GitUrl::from(url.as_str()),
+        &checkout.path().join("beta"),
+        None,
+        GitFetchOptions::default(),
+        Some(Path::new("packages/beta")),
+    )
+    .unwrap_err();
+    assert!(error.0.contains("packages/beta does not exist"));
+}
+
+#[test]
+fn test_toolchain_file_of_the_cli() {
+    let file = PathBuf::from("/work/cmake/aarch64.cmake");
+    let mut apps: Vec<App> = ["C", "Cpp", "UC", "Rust"]
+        .into_iter()
+        .map(|target| {
+            let app: liblingo::package::AppFile = toml::from_str(&format!(
+                "name = \"robot\"\ntarget = \"{target}\"\nmain = \"src/Main.lf\"\nproperties = {{}}"
+            ))
+            .unwrap();
+            app.convert("robot", Path::new("/work"))
+        })
+        .collect();
+    use_toolchain_file(&mut apps, &file);
+
+    for app in &apps[..3] {
+        assert_eq!(app.toolchain.as_ref().unwrap().file.as_ref(), Some(&file));
+        assert_eq!(app.output_root, Path::new("/work/build/aarch64"));
+    }
+    assert!(apps[3].toolchain.is_none());
+}
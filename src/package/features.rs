@@ -0,0 +1,239 @@
+use serde_derive::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::package::{
+    target_properties::{
+        LibraryTargetProperties, LibraryTargetPropertiesFile, MergeTargetProperties,
+    },
+    tree::PackageDetails,
+};
+use crate::util::errors::LingoError;
+
+/// name of the feature that is enabled unless a dependency sets `default-features = false`
+pub const DEFAULT_FEATURE: &str = "default";
+
+/// Entry of the `[features]` table inside the Lingo.toml. A feature enables other features of
+/// the package, optional dependencies and features of dependencies (`dependency/feature`).
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum FeatureFile {
+    Enables(Vec<String>),
+    Table {
+        #[serde(default)]
+        enables: Vec<String>,
+        /// target properties that are added to the library if the feature is enabled
+        properties: Option<LibraryTargetPropertiesFile>,
+    },
+}
+
+#[derive(Clone)]
+pub struct Feature {
+    /// features, optional dependencies and features of dependencies this feature enables
+    pub enables: Vec<String>,
+    /// target properties that are added to the library if the feature is enabled
+    pub properties: Option<LibraryTargetProperties>,
+}
+
+impl FeatureFile {
+    pub fn convert(self, path: &Path) -> Feature {
+        match self {
+            FeatureFile::Enables(enables) => Feature {
+                enables,
+                properties: None,
+            },
+            FeatureFile::Table {
+                enables,
+                properties,
+            } => Feature {
+                enables,
+                properties: properties.map(|properties| properties.from(path)),
+            },
+        }
+    }
+}
+
+/// Features which the dependents of a package asked for
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct FeatureRequest {
+    pub features: BTreeSet<String>,
+    /// true if at least one dependent uses the default features
+    pub default: bool,
+}
+
+impl FeatureRequest {
+    /// the default features of a package
+    pub fn default_features() -> Self {
+        FeatureRequest {
+            features: BTreeSet::new(),
+            default: true,
+        }
+    }
+
+    /// adds the features requested by another dependent
+    pub fn add(&mut self, details: &PackageDetails) {
+        self.features.extend(details.features.iter().cloned());
+        self.default |= details.default_features;
+    }
+}
+
+/// Result of enabling the requested features of a package
+#[derive(Debug, Default)]
+pub struct Activation {
+    /// enabled features, including optional dependencies that were enabled
+    pub features: BTreeSet<String>,
+    /// dependencies that are used, with the features they should enable added
+    pub dependencies: Vec<(String, PackageDetails)>,
+}
+
+/// Enables the requested features of a package and determines which of its dependencies
+/// are used. Optional dependencies are only used if a feature enables them.
+pub fn activate(
+    package: &str,
+    dependencies: &[(String, PackageDetails)],
+    features: &HashMap<String, Vec<String>>,
+    request: &FeatureRequest,
+) -> anyhow::Result<Activation> {
+    let is_optional = |name: &str| {
+        dependencies
+            .iter()
+            .any(|(dependency, details)| dependency == name && details.optional)
+    };
+    let unknown =
+        |feature: &str| LingoError::UnknownFeature(package.to_string(), feature.to_string());
+
+    let mut pending: Vec<String> = request.features.iter().rev().cloned().collect();
+    if request.default && features.contains_key(DEFAULT_FEATURE) {
+        pending.push(DEFAULT_FEATURE.to_string());
+    }
+
+    let mut enabled = BTreeSet::new();
+    let mut dependency_features: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    while let Some(feature) = pending.pop() {
+        if enabled.contains(&feature) {
+            continue;
+        }
+        match features.get(&feature) {
+            Some(enables) => {
+                for entry in enables {
+                    match entry.split_once('/') {
+                        Some((dependency, dependency_feature)) => {
+                            if !dependencies.iter().any(|(name, _)| name == dependency) {
+                                return Err(unknown(entry).into());
+                            }
+                            if is_optional(dependency) {
+                                pending.push(dependency.to_string());
+                            }
+                            dependency_features
+                                .entry(dependency.to_string())
+                                .or_default()
+                                .push(dependency_feature.to_string());
+                        }
+                        None => pending.push(entry.clone()),
+                    }
+                }
+            }
+            // optional dependencies can be enabled like features
+            None if is_optional(&feature) => {}
+            None => return Err(unknown(&feature).into()),
+        }
+        enabled.insert(feature);
+    }
+
+    let dependencies = dependencies
+        .iter()
+        .filter(|(name, details)| !details.optional || enabled.contains(name))
+        .map(|(name, details)| {
+            let mut details = details.clone();
+            if let Some(features) = dependency_features.get(name) {
+                details.features.extend(features.iter().cloned());
+            }
+            (name.clone(), details)
+        })
+        .collect();
+
+    Ok(Activation {
+        features: enabled,
+        dependencies,
+    })
+}
+
+/// adds the target properties of the enabled features to the properties of the library
+pub fn apply(
+    properties: &mut LibraryTargetProperties,
+    features: &HashMap<String, Feature>,
+    enabled: &BTreeSet<String>,
+) -> anyhow::Result<()> {
+    for feature in enabled {
+        if let Some(Feature {
+            properties: Some(feature_properties),
+            ..
+        }) = features.get(feature)
+        {
+            properties.merge(feature_properties)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_activate_features() {
+    let dependencies: Vec<(String, PackageDetails)> =
+        toml::from_str::<HashMap<String, PackageDetails>>(
+            r#"
+logging = { version = "^1.0.0", path = "../logging" }
+mqtt = { version = "^1.0.0", path = "../mqtt", optional = true }
+tracer = { version = "^1.0.0", path = "../tracer", optional = true }
+"#,
+        )
+        .unwrap()
+        .into_iter()
+        .collect();
+    let features = HashMap::from([
+        ("default".to_string(), vec!["tracing".to_string()]),
+        (
+            "tracing".to_string(),
+            vec!["tracer".to_string(), "logging/verbose".to_string()],
+        ),
+        ("transport".to_string(), vec!["mqtt".to_string()]),
+    ]);
+    let used = |activation: &Activation| {
+        let mut names: Vec<String> = activation
+            .dependencies
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    };
+
+    let activation = activate(
+        "lib",
+        &dependencies,
+        &features,
+        &FeatureRequest::default_features(),
+    )
+    .unwrap();
+    assert_eq!(used(&activation), ["logging", "tracer"]);
+    let (_, logging) = activation
+        .dependencies
+        .iter()
+        .find(|(name, _)| name == "logging")
+        .unwrap();
+    assert_eq!(logging.features, ["verbose"]);
+
+    let request = FeatureRequest {
+        features: BTreeSet::from(["transport".to_string()]),
+        default: false,
+    };
+    let activation = activate("lib", &dependencies, &features, &request).unwrap();
+    assert_eq!(used(&activation), ["logging", "mqtt"]);
+    assert!(activation.features.contains("mqtt"));
+
+    let request = FeatureRequest {
+        features: BTreeSet::from(["missing".to_string()]),
+        default: false,
+    };
+    assert!(activate("lib", &dependencies, &features, &request).is_err());
+}
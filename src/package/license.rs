@@ -0,0 +1,245 @@
+use colored::Colorize;
+use serde_derive::{Deserialize, Serialize};
+use versions::Versioning;
+
+use crate::util::errors::LingoError;
+
+/// The `[policy]` section inside the Lingo.toml
+#[derive(Clone, Deserialize, Serialize, Default, Debug)]
+pub struct Policy {
+    /// SPDX identifiers of the licenses dependencies may use, every license is allowed if unset
+    #[serde(rename = "allowed-licenses", default)]
+    pub allowed_licenses: Option<Vec<String>>,
+}
+
+/// Parsed SPDX license expression like `MIT OR Apache-2.0`
+#[derive(Debug, PartialEq)]
+pub enum LicenseExpression {
+    License {
+        /// license identifier, a trailing `+` means this or any later version
+        id: String,
+        /// exception added with `WITH`
+        exception: Option<String>,
+    },
+    And(Box<LicenseExpression>, Box<LicenseExpression>),
+    Or(Box<LicenseExpression>, Box<LicenseExpression>),
+}
+
+/// splits the expression into identifiers, operators and parentheses
+fn tokenize(expression: &str) -> Vec<String> {
+    expression
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, reason: &str) -> LingoError {
+        LingoError::InvalidLicenseExpression(self.expression.to_string(), reason.to_string())
+    }
+
+    fn peek_operator(&self, operator: &str) -> bool {
+        self.tokens
+            .get(self.position)
+            .is_some_and(|token| token.eq_ignore_ascii_case(operator))
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// `and ("OR" and)*`
+    fn or(&mut self) -> anyhow::Result<LicenseExpression> {
+        let mut expression = self.and()?;
+        while self.peek_operator("OR") {
+            self.position += 1;
+            expression = LicenseExpression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    /// `license ("AND" license)*`, AND binds stronger than OR
+    fn and(&mut self) -> anyhow::Result<LicenseExpression> {
+        let mut expression = self.license()?;
+        while self.peek_operator("AND") {
+            self.position += 1;
+            expression = LicenseExpression::And(Box::new(expression), Box::new(self.license()?));
+        }
+        Ok(expression)
+    }
+
+    /// `"(" or ")"` or `id ["WITH" exception]`
+    fn license(&mut self) -> anyhow::Result<LicenseExpression> {
+        let token = self
+            .next()
+            .ok_or_else(|| self.error("expected a license identifier"))?;
+        if token == "(" {
+            let expression = self.or()?;
+            return match self.next() {
+                Some(token) if token == ")" => Ok(expression),
+                _ => Err(self.error("missing closing parenthesis").into()),
+            };
+        }
+        if token == ")"
+            || ["AND", "OR", "WITH"]
+                .iter()
+                .any(|op| token.eq_ignore_ascii_case(op))
+        {
+            return Err(self
+                .error(&format!("expected a license identifier, found {token}"))
+                .into());
+        }
+
+        let exception = if self.peek_operator("WITH") {
+            self.position += 1;
+            Some(
+                self.next()
+                    .filter(|exception| exception != "(" && exception != ")")
+                    .ok_or_else(|| self.error("expected an exception after WITH"))?,
+            )
+        } else {
+            None
+        };
+        Ok(LicenseExpression::License {
+            id: token,
+            exception,
+        })
+    }
+}
+
+impl LicenseExpression {
+    pub fn parse(expression: &str) -> anyhow::Result<LicenseExpression> {
+        let mut parser = Parser {
+            expression,
+            tokens: tokenize(expression),
+            position: 0,
+        };
+        let parsed = parser.or()?;
+        if let Some(token) = parser.next() {
+            return Err(parser.error(&format!("unexpected {token}")).into());
+        }
+        Ok(parsed)
+    }
+
+    /// true if the license terms can be met using only the allowed licenses. SPDX identifiers
+    /// are compared case-insensitively, `GPL-2.0+` is allowed if `GPL-2.0` is.
+    pub fn is_allowed(&self, allowed: &[String]) -> bool {
+        let contains = |id: &str| allowed.iter().any(|entry| entry.eq_ignore_ascii_case(id));
+        match self {
+            LicenseExpression::License { id, exception } => {
+                let with_exception = exception
+                    .as_ref()
+                    .is_some_and(|exception| contains(&format!("{id} WITH {exception}")));
+                with_exception || contains(id) || contains(id.trim_end_matches('+'))
+            }
+            LicenseExpression::And(left, right) => {
+                left.is_allowed(allowed) && right.is_allowed(allowed)
+            }
+            LicenseExpression::Or(left, right) => {
+                left.is_allowed(allowed) || right.is_allowed(allowed)
+            }
+        }
+    }
+}
+
+/// License of a resolved package
+#[derive(Debug)]
+pub struct PackageLicense {
+    pub name: String,
+    pub version: Versioning,
+    /// `license` field from the Lingo.toml of the package
+    pub license: Option<String>,
+    /// source of the package as recorded in Lingo.lock
+    pub source: String,
+}
+
+/// Checks the license of every package against the policy. Packages without a license or with
+/// a license that isn't allowed are reported together.
+pub fn check(packages: &[PackageLicense], policy: &Policy) -> anyhow::Result<()> {
+    let Some(allowed) = &policy.allowed_licenses else {
+        return Ok(());
+    };
+
+    let mut violations = Vec::new();
+    for package in packages {
+        let Some(license) = &package.license else {
+            violations.push(format!(
+                "{} {} doesn't declare a license",
+                package.name, package.version
+            ));
+            continue;
+        };
+        match LicenseExpression::parse(license) {
+            Ok(expression) if expression.is_allowed(allowed) => {}
+            Ok(_) => violations.push(format!(
+                "{} {} is licensed under {license}",
+                package.name, package.version
+            )),
+            Err(e) => violations.push(format!("{} {}: {e}", package.name, package.version)),
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(LingoError::LicensePolicyViolation(violations).into())
+    }
+}
+
+/// prints the license and the source of every package
+pub fn print(packages: &[PackageLicense]) {
+    for package in packages {
+        let license = match &package.license {
+            Some(license) if LicenseExpression::parse(license).is_ok() => license.normal(),
+            Some(license) => format!("{license} (invalid SPDX expression)").red(),
+            None => "no license".red(),
+        };
+        println!(
+            "{} {} {} ({})",
+            package.name.bold(),
+            package.version,
+            license,
+            package.source
+        );
+    }
+}
+
+#[test]
+fn test_license_expression() {
+    let allowed = ["MIT".to_string(), "Apache-2.0".to_string()];
+
+    let expression = LicenseExpression::parse("MIT OR GPL-3.0-only").unwrap();
+    assert!(expression.is_allowed(&allowed));
+
+    let expression = LicenseExpression::parse("mit AND (Apache-2.0 OR GPL-3.0-only)").unwrap();
+    assert!(expression.is_allowed(&allowed));
+
+    let expression = LicenseExpression::parse("MIT AND GPL-2.0+ WITH Classpath-exception-2.0");
+    assert_eq!(
+        expression.unwrap(),
+        LicenseExpression::And(
+            Box::new(LicenseExpression::License {
+                id: "MIT".to_string(),
+                exception: None
+            }),
+            Box::new(LicenseExpression::License {
+                id: "GPL-2.0+".to_string(),
+                exception: Some("Classpath-exception-2.0".to_string())
+            })
+        )
+    );
+
+    assert!(LicenseExpression::parse("MIT OR").is_err());
+    assert!(LicenseExpression::parse("(MIT").is_err());
+    assert!(LicenseExpression::parse("MIT Apache-2.0").is_err());
+}
@@ -1,33 +1,36 @@
 use crate::util::sha1dir;
 use colored::Colorize;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use versions::Versioning;
+use versions::{Requirement, Versioning};
 
 use log::error;
 use serde::de::Error as DeserializationError;
 use serde::ser::Error as SerializationError;
 use std::cmp::PartialEq;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt::Display;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::GitCloneAndCheckoutCap;
+use crate::{DownloadCapability, GitCloneAndCheckoutCap};
 
-use crate::package::management::copy_dir_all;
+use crate::package::management::{copy_dir_all, library_location, link_dir, remove_dir_or_link};
 use crate::package::{
-    deserialize_version, serialize_version,
+    deserialize_version, features,
+    license::PackageLicense,
+    serialize_version,
     target_properties::{LibraryTargetProperties, MergeTargetProperties},
     tree::{DependencyTreeNode, PackageDetails, ProjectSource},
     ConfigFile,
 };
 use crate::util::errors::LingoError;
+use crate::util::relative_path;
 
 pub struct ParseLockSourceError {}
 
 /// Different package sources types, available inside the lock file.
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum PackageLockSourceType {
     REGISTRY,
     GIT,
@@ -36,7 +39,7 @@ pub enum PackageLockSourceType {
 }
 
 /// Struct that saves the source uri string
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PackageLockSource {
     pub source_type: PackageLockSourceType,
     pub uri: String,
@@ -77,6 +80,7 @@ impl From<ProjectSource> for PackageLockSourceType {
             ProjectSource::Git(_) => Self::GIT,
             ProjectSource::TarBall(_) => Self::TARBALL,
             ProjectSource::Path(_) => Self::PATH,
+            ProjectSource::Registry(_) => Self::REGISTRY,
         }
     }
 }
@@ -115,7 +119,7 @@ impl FromStr for PackageLockSource {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct PackageLock {
     pub name: String,
     #[serde(
@@ -124,15 +128,114 @@ pub struct PackageLock {
     )]
     pub version: Versioning,
     pub source: PackageLockSource,
+    /// checksum of the package, path dependencies are used as they are and have none
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub checksum: String,
+    /// sha256 of the downloaded archive for tarball sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// directory inside the downloaded archive that contains the package
+    #[serde(
+        rename = "strip-prefix",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub strip_prefix: Option<PathBuf>,
+    /// directory inside the git repository that contains the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<PathBuf>,
+    /// the source was taken from the `[patch]` table of the Lingo.toml
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub patched: bool,
+    /// enabled features of this package
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub features: BTreeSet<String>,
+    /// direct dependencies of this package with the requested version requirement
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, String>,
+}
+
+/// State of the copy of a locked package inside the build directory
+#[derive(Debug, PartialEq)]
+pub enum LockedPackageState {
+    /// contents match the checksum from Lingo.lock
+    Intact,
+    /// the package has not been fetched
+    Missing,
+    /// the package contains a different version than Lingo.lock
+    Stale(Versioning),
+    /// contents don't match the checksum from Lingo.lock, contains the actual checksum
+    Tampered(String),
 }
 
-impl From<DependencyTreeNode> for PackageLock {
-    fn from(value: DependencyTreeNode) -> Self {
-        let uri = match &value.package.mutual_exclusive {
-            ProjectSource::Git(git) => git.to_string(),
-            ProjectSource::TarBall(tar) => tar.to_string(),
-            ProjectSource::Path(path) => format!("{:?}", path),
+impl PackageLock {
+    /// location of a path dependency, relative paths are resolved against the project root
+    fn local_path(&self, root: &Path) -> Option<PathBuf> {
+        match PackageDetails::try_from(self).ok()?.relative_to(root) {
+            PackageDetails {
+                mutual_exclusive: ProjectSource::Path(path),
+                ..
+            } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// compares the copy of the package at the given location with this lock entry
+    pub fn check(&self, location: &Path) -> anyhow::Result<LockedPackageState> {
+        let state = self.check_version(location)?;
+        if state != LockedPackageState::Intact {
+            return Ok(state);
+        }
+
+        let hash = sha1dir::checksum_dir(location, false)?.to_string();
+        if hash != self.checksum {
+            return Ok(LockedPackageState::Tampered(hash));
+        }
+
+        Ok(LockedPackageState::Intact)
+    }
+
+    /// compares only the version of the package at the given location with this lock entry
+    fn check_version(&self, location: &Path) -> anyhow::Result<LockedPackageState> {
+        let lingo_toml = location.join("Lingo.toml");
+        if !lingo_toml.exists() {
+            return Ok(LockedPackageState::Missing);
+        }
+
+        let version = toml::from_str::<ConfigFile>(&fs::read_to_string(lingo_toml)?)?
+            .package
+            .version;
+        if version != self.version {
+            return Ok(LockedPackageState::Stale(version));
+        }
+
+        Ok(LockedPackageState::Intact)
+    }
+}
+
+/// location of the source as it is written into the lock file, paths are relative to the
+/// project root so that the lock file can be shared
+fn source_uri(source: &ProjectSource, root: &Path) -> String {
+    match source {
+        ProjectSource::Git(git) => git.to_string(),
+        ProjectSource::TarBall(tar) => tar.to_string(),
+        ProjectSource::Path(path) => {
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            relative_path(&path, root).display().to_string()
+        }
+        ProjectSource::Registry(index) => index
+            .as_ref()
+            .map(|index| index.to_string())
+            .unwrap_or_default(),
+    }
+}
+
+impl PackageLock {
+    fn new(value: DependencyTreeNode, root: &Path) -> Self {
+        let uri = source_uri(&value.package.mutual_exclusive, root);
+        let checksum = match value.package.mutual_exclusive {
+            ProjectSource::Path(_) => String::new(),
+            _ => value.hash,
         };
 
         PackageLock {
@@ -143,8 +246,29 @@ impl From<DependencyTreeNode> for PackageLock {
                 uri,
                 rev: value.package.git_rev,
             },
-            checksum: value.hash,
+            checksum,
+            sha256: value.package.sha256,
+            strip_prefix: value.package.strip_prefix,
+            subdir: value.package.subdir,
+            patched: value.package.patched,
+            features: value.features,
+            dependencies: value
+                .requirements
+                .iter()
+                .map(|(name, requirement)| (name.clone(), requirement.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// formats the source like it is written into the lock file
+impl Display for PackageLockSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}+{}", self.source_type, self.uri)?;
+        if let Some(rev) = &self.rev {
+            write!(f, "#{rev}")?;
         }
+        Ok(())
     }
 }
 
@@ -182,65 +306,286 @@ impl Serialize for PackageLockSource {
     }
 }
 
-#[derive(Deserialize, Serialize, Default, Debug)]
+/// Version of the Lingo.lock format written by this version of lingo
+pub const LOCK_FILE_VERSION: u32 = 2;
+
+/// Layout of the Lingo.lock file
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum LockFileFormat {
+    Versioned {
+        version: u32,
+        #[serde(rename = "package", default)]
+        packages: Vec<PackageLock>,
+    },
+    /// lock files written before the format was versioned contain one table per package
+    Unversioned(HashMap<String, PackageLock>),
+}
+
+#[derive(Clone, Deserialize, Serialize, Default, Debug)]
+#[serde(try_from = "LockFileFormat", into = "LockFileFormat")]
 pub struct DependencyLock {
     /// mapping from package name to location
-    #[serde(flatten)]
-    pub dependencies: HashMap<String, PackageLock>,
+    pub dependencies: BTreeMap<String, PackageLock>,
+
+    /// format version of the file this lock was read from
+    format_version: u32,
+
+    /// directory containing the Lingo.lock, paths in the lock are relative to it
+    root: PathBuf,
 
     /// this will be populated when the project is successfully loaded from the lock file
-    #[serde(skip)]
     loaded_dependencies: Vec<DependencyTreeNode>,
 }
 
+impl TryFrom<LockFileFormat> for DependencyLock {
+    type Error = String;
+
+    fn try_from(value: LockFileFormat) -> Result<Self, Self::Error> {
+        let (format_version, packages) = match value {
+            LockFileFormat::Versioned { version, .. } if version > LOCK_FILE_VERSION => {
+                return Err(format!(
+                    "Lingo.lock has format version {version} but this version of lingo only supports up to version {LOCK_FILE_VERSION}"
+                ));
+            }
+            LockFileFormat::Versioned { version, packages } => (version, packages),
+            LockFileFormat::Unversioned(packages) => (1, packages.into_values().collect()),
+        };
+
+        Ok(DependencyLock {
+            dependencies: packages
+                .into_iter()
+                .map(|package| (package.name.clone(), package))
+                .collect(),
+            format_version,
+            root: PathBuf::new(),
+            loaded_dependencies: vec![],
+        })
+    }
+}
+
+impl From<DependencyLock> for LockFileFormat {
+    fn from(value: DependencyLock) -> Self {
+        LockFileFormat::Versioned {
+            version: LOCK_FILE_VERSION,
+            // the map is ordered by name, which keeps the file stable between runs
+            packages: value.dependencies.into_values().collect(),
+        }
+    }
+}
+
 impl DependencyLock {
-    pub(crate) fn create(selected_dependencies: Vec<DependencyTreeNode>) -> DependencyLock {
-        let mut map = HashMap::new();
+    pub(crate) fn create(
+        selected_dependencies: Vec<DependencyTreeNode>,
+        root: &Path,
+    ) -> DependencyLock {
+        let mut map = BTreeMap::new();
         for dependency in &selected_dependencies {
             map.insert(
                 dependency.name.clone(),
-                PackageLock::from(dependency.clone()),
+                PackageLock::new(dependency.clone(), root),
             );
         }
         Self {
             dependencies: map,
+            format_version: LOCK_FILE_VERSION,
+            root: root.to_path_buf(),
             loaded_dependencies: selected_dependencies,
         }
     }
 
+    /// reads the given Lingo.lock
+    pub fn read(lock_file: &Path) -> anyhow::Result<DependencyLock> {
+        let mut lock = toml::from_str::<DependencyLock>(&fs::read_to_string(lock_file)?)?;
+        lock.root = fs::canonicalize(lock_file.parent().unwrap_or(Path::new(".")))?;
+        Ok(lock)
+    }
+
+    /// true if the lock was read from an older format and should be written again
+    pub fn needs_migration(&self) -> bool {
+        self.format_version < LOCK_FILE_VERSION
+    }
+
+    /// checks that every dependency requested in the Lingo.toml is locked with a matching version,
+    /// that every locked package is still required and that the locked packages use the sources
+    /// from the `[patch]` table
+    pub fn satisfies(
+        &self,
+        dependencies: &[(String, PackageDetails)],
+        patches: &HashMap<String, PackageDetails>,
+    ) -> anyhow::Result<()> {
+        for (name, lock) in &self.dependencies {
+            let replaced_by = patches.get(name).map(|patch| {
+                (
+                    PackageLockSourceType::from(patch.mutual_exclusive.clone()),
+                    source_uri(&patch.mutual_exclusive, &self.root),
+                )
+            });
+            let locked = (lock.source.source_type.clone(), lock.source.uri.clone());
+            match replaced_by {
+                Some(patch) if !lock.patched || patch != locked => {
+                    return Err(anyhow::anyhow!("patch for {name} changed"));
+                }
+                None if lock.patched => {
+                    return Err(anyhow::anyhow!("patch for {name} was removed"));
+                }
+                _ => {}
+            }
+        }
+
+        // older formats don't record the edges, their packages are checked after loading them
+        if !self.needs_migration() {
+            let mut reachable = BTreeSet::new();
+            let mut pending: Vec<&String> = dependencies.iter().map(|(name, _)| name).collect();
+            while let Some(name) = pending.pop() {
+                if reachable.insert(name) {
+                    if let Some(lock) = self.dependencies.get(name) {
+                        pending.extend(lock.dependencies.keys());
+                    }
+                }
+            }
+            if let Some(name) = self
+                .dependencies
+                .keys()
+                .find(|name| !reachable.contains(name))
+            {
+                return Err(anyhow::anyhow!("{name} is locked but no longer required"));
+            }
+        }
+
+        for (name, details) in dependencies {
+            match self.dependencies.get(name) {
+                Some(lock) if details.version.matches(&lock.version) => {
+                    let missing: Vec<&str> = details
+                        .features
+                        .iter()
+                        .filter(|feature| !lock.features.contains(*feature))
+                        .map(String::as_str)
+                        .collect();
+                    if !missing.is_empty() {
+                        return Err(anyhow::anyhow!(
+                            "{name} is locked without the features {}",
+                            missing.join(", ")
+                        ));
+                    }
+                }
+                Some(lock) => {
+                    return Err(LingoError::LingoVersionMismatch(format!(
+                        "{name} is locked at version {} which doesn't satisfy {}",
+                        lock.version, details.version
+                    ))
+                    .into())
+                }
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "dependency {name} is missing in Lingo.lock"
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads the locked packages into the include folder and checks their checksums. Path
+    /// dependencies are linked in place and have no checksum, so editing them keeps the lock. The
+    /// checksums of older lock formats can't be reproduced, so they are replaced together with
+    /// the missing dependency edges. Returns the names of the packages whose checksum was updated.
     pub fn init(
         &mut self,
         lfc_include_folder: &Path,
+        library_folder: &Path,
+        offline: bool,
         git_clone_and_checkout_cap: &GitCloneAndCheckoutCap,
-    ) -> anyhow::Result<()> {
-        for (_, lock) in self.dependencies.iter() {
+        download_cap: &DownloadCapability,
+    ) -> anyhow::Result<Vec<String>> {
+        let legacy = self.needs_migration();
+        let locked: BTreeSet<String> = self.dependencies.keys().cloned().collect();
+        let mut updated = Vec::new();
+        for lock in self.dependencies.values_mut() {
             let temp = lfc_include_folder.join(&lock.name);
-            // the Lingo.toml for this dependency doesnt exists, hence we need to fetch this package
-            if !temp.join("Lingo.toml").exists() {
-                let mut details = PackageDetails::try_from(&lock.source)?;
 
-                details
-                    .fetch(&temp, git_clone_and_checkout_cap)
-                    .expect("cannot pull package");
-            }
+            let hash = match lock.local_path(&self.root) {
+                Some(path) => {
+                    let source = fs::canonicalize(&path).map_err(|e| {
+                        anyhow::anyhow!("cannot find {} at {}: {e}", lock.name, path.display())
+                    })?;
+                    link_dir(&source, &temp)?;
+                    // older lock files recorded a checksum for path dependencies too
+                    lock.checksum.clear();
+                    String::new()
+                }
+                None => {
+                    // the package was a path dependency before
+                    if temp.is_symlink() {
+                        remove_dir_or_link(&temp)?;
+                    }
+                    let already_fetched =
+                        library_location(library_folder, &lock.name, &lock.version);
 
-            let hash = sha1dir::checksum_current_dir(&temp, false);
+                    // the Lingo.toml for this dependency doesnt exists, hence we need to fetch this package
+                    if !temp.join("Lingo.toml").exists() {
+                        fs::create_dir_all(&temp)?;
 
-            if hash.to_string() != lock.checksum {
-                error!("checksum does not match aborting!");
-            }
+                        // older versions of lingo placed fetched packages under their checksum
+                        let legacy_cache = library_folder.join(&lock.checksum);
+                        if legacy
+                            && !lock.checksum.is_empty()
+                            && !already_fetched.exists()
+                            && legacy_cache.join("Lingo.toml").exists()
+                        {
+                            fs::rename(&legacy_cache, &already_fetched)?;
+                        }
+
+                        if already_fetched.join("Lingo.toml").exists() {
+                            // the package was fetched during an earlier resolution
+                            copy_dir_all(&already_fetched, &temp)?;
+                        } else {
+                            let mut details = PackageDetails::try_from(&*lock)?;
+
+                            if offline && details.requires_network() {
+                                return Err(LingoError::OfflineFetch(lock.name.clone()).into());
+                            }
+                            details.git_fetch.offline = offline;
+
+                            details.fetch(
+                                &lock.name,
+                                &temp,
+                                git_clone_and_checkout_cap,
+                                download_cap,
+                            )?;
+                        }
+                    }
+
+                    let hash = sha1dir::checksum_dir(&temp, false)?.to_string();
+                    if legacy {
+                        if hash != lock.checksum {
+                            lock.checksum = hash.clone();
+                            updated.push(lock.name.clone());
+                        }
+                    } else if hash != lock.checksum {
+                        return Err(LingoError::ChecksumMismatch(
+                            lock.name.clone(),
+                            lock.checksum.clone(),
+                            hash,
+                        )
+                        .into());
+                    }
+                    hash
+                }
+            };
 
             let lingo_toml_text = fs::read_to_string(temp.join("Lingo.toml"))?;
-            let read_toml = toml::from_str::<ConfigFile>(&lingo_toml_text)?.to_config(&temp);
+            let read_toml = toml::from_str::<ConfigFile>(&lingo_toml_text)?.to_config(&temp)?;
 
-            println!(
+            eprintln!(
                 "{} {} ... {}",
                 "Reading".green().bold(),
                 lock.name,
                 read_toml.package.version
             );
 
-            let lib = match read_toml.library {
+            let mut lib = match read_toml.library {
                 Some(value) => value,
                 None => {
                     // error we expected a library here
@@ -250,6 +595,16 @@ impl DependencyLock {
                     .into());
                 }
             };
+            features::apply(&mut lib.properties, &read_toml.features, &lock.features)?;
+
+            if legacy {
+                lock.dependencies = read_toml
+                    .dependencies
+                    .iter()
+                    .filter(|(name, _)| locked.contains(*name))
+                    .map(|(name, details)| (name.clone(), details.version.to_string()))
+                    .collect();
+            }
 
             self.loaded_dependencies.push(DependencyTreeNode {
                 name: read_toml.package.name.clone(),
@@ -259,34 +614,236 @@ impl DependencyLock {
                     mutual_exclusive: ProjectSource::Path(PathBuf::new()),
                     git_tag: None,
                     git_rev: None,
+                    git_fetch: Default::default(),
+                    subdir: None,
+                    patched: false,
+                    sha256: None,
+                    strip_prefix: None,
+                    features: vec![],
+                    optional: false,
+                    default_features: false,
                 },
                 location: temp.clone(),
                 include_path: lib.location.clone(),
-                hash: lock.checksum.clone(),
+                hash,
                 dependencies: vec![],
+                requirements: lock
+                    .dependencies
+                    .iter()
+                    .filter_map(|(name, requirement)| {
+                        Requirement::new(requirement).map(|requirement| (name.clone(), requirement))
+                    })
+                    .collect(),
+                features: lock.features.clone(),
+                license: read_toml.package.license.clone(),
                 properties: lib.properties.clone(),
             });
         }
 
-        Ok(())
+        Ok(updated)
     }
 
-    pub fn create_library_folder(
+    /// Recomputes the checksum of every locked package and reports packages that are missing,
+    /// contain another version or were modified. With `repair` these packages are fetched
+    /// again from the source recorded in the lock file.
+    pub fn verify(
         &self,
-        source_path: &Path,
-        target_path: &PathBuf,
+        lfc_include_folder: &Path,
+        library_folder: &Path,
+        repair: bool,
+        git_clone_and_checkout_cap: &GitCloneAndCheckoutCap,
+        download_cap: &DownloadCapability,
     ) -> anyhow::Result<()> {
+        if self.needs_migration() {
+            return Err(anyhow::anyhow!(
+                "Lingo.lock uses an older format whose checksums can't be verified, run `lingo build` to migrate it"
+            ));
+        }
+
+        let mut names: Vec<&String> = self.dependencies.keys().collect();
+        names.sort();
+
+        let mut broken = Vec::new();
+        for name in names {
+            let lock = &self.dependencies[name];
+            let location = lfc_include_folder.join(name);
+
+            // path dependencies are used as they are, only their version is locked
+            if let Some(path) = lock.local_path(&self.root) {
+                match lock.check_version(&path)? {
+                    LockedPackageState::Missing => {
+                        println!(
+                            "{} {} {} (path dependency {})",
+                            "Missing".yellow().bold(),
+                            name,
+                            lock.version,
+                            path.display()
+                        );
+                        broken.push(name.clone());
+                    }
+                    LockedPackageState::Stale(version) => {
+                        println!(
+                            "{} {} {} (locked {})",
+                            "Stale".yellow().bold(),
+                            name,
+                            version,
+                            lock.version
+                        );
+                        broken.push(name.clone());
+                    }
+                    _ => println!("{} {} {}", "Verified".green().bold(), name, lock.version),
+                }
+                continue;
+            }
+
+            let cache = library_location(library_folder, name, &lock.version);
+
+            let mut state = lock.check(&location)?;
+            // the copy in the library folder is used to restore the include folder
+            if state == LockedPackageState::Intact && cache.exists() {
+                state = lock.check(&cache)?;
+            }
+
+            match &state {
+                LockedPackageState::Intact => {
+                    println!("{} {} {}", "Verified".green().bold(), name, lock.version);
+                    continue;
+                }
+                LockedPackageState::Missing => {
+                    println!("{} {} {}", "Missing".yellow().bold(), name, lock.version)
+                }
+                LockedPackageState::Stale(version) => println!(
+                    "{} {} {} (locked {})",
+                    "Stale".yellow().bold(),
+                    name,
+                    version,
+                    lock.version
+                ),
+                LockedPackageState::Tampered(hash) => println!(
+                    "{} {} {} (checksum {} expected {})",
+                    "Tampered".red().bold(),
+                    name,
+                    lock.version,
+                    hash,
+                    lock.checksum
+                ),
+            }
+
+            if !repair {
+                broken.push(name.clone());
+                continue;
+            }
+
+            let _ = fs::remove_dir_all(&location);
+            let _ = fs::remove_dir_all(&cache);
+            fs::create_dir_all(&location)?;
+            PackageDetails::try_from(lock)?.fetch(
+                name,
+                &location,
+                git_clone_and_checkout_cap,
+                download_cap,
+            )?;
+
+            // the source itself no longer provides the locked contents
+            match lock.check(&location)? {
+                LockedPackageState::Intact => {
+                    println!("{} {} {}", "Repaired".green().bold(), name, lock.version)
+                }
+                LockedPackageState::Tampered(hash) => {
+                    return Err(LingoError::ChecksumMismatch(
+                        name.clone(),
+                        lock.checksum.clone(),
+                        hash,
+                    )
+                    .into())
+                }
+                LockedPackageState::Stale(version) => {
+                    return Err(LingoError::LingoVersionMismatch(format!(
+                        "expected {name} {} got version {version}",
+                        lock.version
+                    ))
+                    .into())
+                }
+                LockedPackageState::Missing => {
+                    return Err(LingoError::NoLibraryInLingoToml(
+                        location.join("Lingo.toml").display().to_string(),
+                    )
+                    .into())
+                }
+            }
+        }
+
+        if broken.is_empty() {
+            Ok(())
+        } else {
+            Err(LingoError::LockVerificationFailed(broken).into())
+        }
+    }
+
+    pub fn create_library_folder(&self, target_path: &PathBuf) -> anyhow::Result<()> {
         fs::create_dir_all(target_path)?;
-        for (_, dep) in self.dependencies.iter() {
-            let local_source = source_path.join(&dep.checksum);
+        for dep in &self.loaded_dependencies {
             let find_source = target_path.clone().join(&dep.name);
+            if let ProjectSource::Path(_) = dep.package.mutual_exclusive {
+                link_dir(&dep.location, &find_source)?;
+                continue;
+            }
+            if find_source.is_symlink() {
+                remove_dir_or_link(&find_source)?;
+            }
             fs::create_dir_all(&find_source)?;
-            copy_dir_all(&local_source, &find_source)?;
+            copy_dir_all(&dep.location, &find_source)?;
         }
 
         Ok(())
     }
 
+    /// license and source of every loaded package
+    pub fn licenses(&self) -> Vec<PackageLicense> {
+        let mut licenses: Vec<PackageLicense> = self
+            .loaded_dependencies
+            .iter()
+            .map(|package| PackageLicense {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                license: package.license.clone(),
+                source: self
+                    .dependencies
+                    .get(&package.name)
+                    .map(|lock| lock.source.to_string())
+                    .unwrap_or_default(),
+            })
+            .collect();
+        licenses.sort_by(|a, b| a.name.cmp(&b.name));
+        licenses
+    }
+
+    /// Absolute path of the directory with the LF files of every loaded library by package name.
+    /// The libraries are made available to lfc inside the `lfc_include_folder`.
+    pub fn include_paths(
+        &self,
+        lfc_include_folder: &Path,
+    ) -> anyhow::Result<BTreeMap<String, PathBuf>> {
+        self.loaded_dependencies
+            .iter()
+            .map(|dep| {
+                let relative = dep
+                    .include_path
+                    .strip_prefix(&dep.location)
+                    .unwrap_or(&dep.include_path);
+                let path = lfc_include_folder.join(&dep.name).join(relative);
+                let path = fs::canonicalize(&path).map_err(|e| {
+                    anyhow::anyhow!(
+                        "cannot find the library of {} at {}: {e}",
+                        dep.name,
+                        path.display()
+                    )
+                })?;
+                Ok((dep.name.clone(), path))
+            })
+            .collect()
+    }
+
     pub fn aggregate_target_properties(&self) -> anyhow::Result<LibraryTargetProperties> {
         let mut i = LibraryTargetProperties::default();
         for tp in &self.loaded_dependencies {
@@ -296,3 +853,230 @@ impl DependencyLock {
         Ok(i)
     }
 }
+
+#[test]
+fn test_lock_file_migration() {
+    let legacy = r#"
+[zeta]
+name = "zeta"
+version = "0.1.0"
+source = "path+\"../zeta\""
+checksum = "aa"
+
+[alpha]
+name = "alpha"
+version = "1.0.0"
+source = "git+https://example.com/alpha.git#0123abcd"
+checksum = "bb"
+"#;
+    let lock = toml::from_str::<DependencyLock>(legacy).unwrap();
+    assert!(lock.needs_migration());
+
+    let migrated = toml::to_string(&lock).unwrap();
+    assert!(migrated.starts_with(&format!("version = {LOCK_FILE_VERSION}")));
+    assert!(migrated.find("alpha").unwrap() < migrated.find("zeta").unwrap());
+
+    let lock = toml::from_str::<DependencyLock>(&migrated).unwrap();
+    assert!(!lock.needs_migration());
+    assert_eq!(lock.dependencies.len(), 2);
+
+    assert!(toml::from_str::<DependencyLock>("version = 1000\n").is_err());
+}
+
+#[test]
+fn test_lock_satisfies() {
+    let lock = toml::from_str::<DependencyLock>(
+        r#"
+version = 2
+
+[[package]]
+name = "alpha"
+version = "1.0.0"
+source = "git+https://example.com/alpha.git#0123abcd"
+checksum = "aa"
+dependencies = { beta = ">=0.1" }
+
+[[package]]
+name = "beta"
+version = "0.2.0"
+source = "git+https://example.com/beta.git#4567cdef"
+checksum = "bb"
+
+[[package]]
+name = "gamma"
+version = "0.3.0"
+source = "git+https://example.com/gamma.git#89abcdef"
+checksum = "cc"
+"#,
+    )
+    .unwrap();
+    let dependency = |name: &str| {
+        let details: PackageDetails = toml::from_str(&format!(
+            "version = \">=0.1\"\ngit = \"https://example.com/{name}.git\""
+        ))
+        .unwrap();
+        (name.to_string(), details)
+    };
+
+    // gamma was removed from the Lingo.toml but is still locked
+    let error = lock
+        .satisfies(&[dependency("alpha")], &HashMap::new())
+        .unwrap_err();
+    assert!(error.to_string().contains("gamma"));
+
+    lock.satisfies(&[dependency("alpha"), dependency("gamma")], &HashMap::new())
+        .unwrap();
+}
+
+/// writes a C library package with a single reactor file
+#[cfg(test)]
+pub(crate) fn write_test_library(
+    location: &Path,
+    name: &str,
+    version: &str,
+    dependencies: &str,
+    reactor: &str,
+) {
+    fs::create_dir_all(location.join("src/lib")).unwrap();
+    fs::write(
+        location.join("Lingo.toml"),
+        format!(
+            "[package]\nname = \"{name}\"\nversion = \"{version}\"\n\n[dependencies]\n{dependencies}\n[lib]\nlocation = \"./src/lib\"\ntarget = \"C\"\n\n[lib.properties]\n"
+        ),
+    )
+    .unwrap();
+    fs::write(location.join("src/lib/Lib.lf"), reactor).unwrap();
+}
+
+#[test]
+fn test_lock_checksum_mismatch() {
+    use std::cell::RefCell;
+
+    let project = tempfile::tempdir().unwrap();
+    let include = project.path().join("build/lfc_include");
+    let library = project.path().join("build/lfc_libraries");
+    fs::create_dir_all(&library).unwrap();
+
+    let published = project.path().join("published");
+    write_test_library(&published, "alpha", "1.0.0", "", "target C\n");
+    let checksum = sha1dir::checksum_dir(&published, false)
+        .unwrap()
+        .to_string();
+    let lock_file = project.path().join("Lingo.lock");
+    fs::write(
+        &lock_file,
+        format!(
+            r#"
+version = 2
+
+[[package]]
+name = "alpha"
+version = "1.0.0"
+source = "git+https://example.com/alpha.git#0123abcd"
+checksum = "{checksum}"
+"#
+        ),
+    )
+    .unwrap();
+
+    // the repository serves whatever is published at the time
+    let reactor = RefCell::new("target C\n");
+    let clone: GitCloneAndCheckoutCap = Box::new(|_, location, _, _, _| {
+        write_test_library(location, "alpha", "1.0.0", "", &reactor.borrow());
+        Ok(Some("0123abcd".to_string()))
+    });
+    let download: DownloadCapability =
+        Box::new(|url| Err(crate::DownloadError::NotFound(url.to_string())));
+
+    let mut lock = DependencyLock::read(&lock_file).unwrap();
+    assert!(lock
+        .init(&include, &library, false, &clone, &download)
+        .unwrap()
+        .is_empty());
+    lock.verify(&include, &library, false, &clone, &download)
+        .unwrap();
+
+    // modified copies are reported and restored with repair
+    fs::write(include.join("alpha/src/lib/Lib.lf"), "target Cpp\n").unwrap();
+    let error = lock
+        .verify(&include, &library, false, &clone, &download)
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<LingoError>(),
+        Some(LingoError::LockVerificationFailed(names)) if names == &["alpha"]
+    ));
+    lock.verify(&include, &library, true, &clone, &download)
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(include.join("alpha/src/lib/Lib.lf")).unwrap(),
+        "target C\n"
+    );
+
+    // the source changed after the package was locked
+    *reactor.borrow_mut() = "target C;\n";
+    fs::remove_dir_all(&include).unwrap();
+    let mut lock = DependencyLock::read(&lock_file).unwrap();
+    let error = lock
+        .init(&include, &library, false, &clone, &download)
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<LingoError>(),
+        Some(LingoError::ChecksumMismatch(name, expected, _)) if name == "alpha" && expected == &checksum
+    ));
+}
+
+#[test]
+fn test_path_dependency_has_no_checksum() {
+    let project = tempfile::tempdir().unwrap();
+    let include = project.path().join("build/lfc_include");
+    let library = project.path().join("build/lfc_libraries");
+    fs::create_dir_all(&library).unwrap();
+    write_test_library(
+        &project.path().join("alpha"),
+        "alpha",
+        "1.0.0",
+        "",
+        "target C\n",
+    );
+
+    // lock files of earlier versions recorded a checksum for path dependencies
+    let lock_file = project.path().join("Lingo.lock");
+    fs::write(
+        &lock_file,
+        "version = 2\n\n[[package]]\nname = \"alpha\"\nversion = \"1.0.0\"\nsource = \"path+alpha\"\nchecksum = \"aa\"\n",
+    )
+    .unwrap();
+    let clone: GitCloneAndCheckoutCap =
+        Box::new(|_, _, _, _, _| panic!("path dependencies are not cloned"));
+    let download: DownloadCapability =
+        Box::new(|url| Err(crate::DownloadError::NotFound(url.to_string())));
+
+    let mut lock = DependencyLock::read(&lock_file).unwrap();
+    assert!(lock
+        .init(&include, &library, false, &clone, &download)
+        .unwrap()
+        .is_empty());
+    assert!(!toml::to_string(&lock).unwrap().contains("checksum"));
+
+    // editing the library neither changes the lock nor fails verification
+    fs::write(project.path().join("alpha/src/lib/Lib.lf"), "target C;\n").unwrap();
+    let mut lock = DependencyLock::read(&lock_file).unwrap();
+    assert!(lock
+        .init(&include, &library, false, &clone, &download)
+        .unwrap()
+        .is_empty());
+    lock.verify(&include, &library, false, &clone, &download)
+        .unwrap();
+
+    // but the version is still locked
+    write_test_library(
+        &project.path().join("alpha"),
+        "alpha",
+        "2.0.0",
+        "",
+        "target C\n",
+    );
+    assert!(lock
+        .verify(&include, &library, false, &clone, &download)
+        .is_err());
+}
@@ -1,10 +1,12 @@
 use colored::Colorize;
-use log::error;
-use versions::{Requirement, Versioning};
+use log::{error, warn};
+use sha2::{Digest, Sha256};
+use versions::Versioning;
 
+use crate::util::archive::{self, ArchiveFormat};
 use crate::util::sha1dir;
-use crate::{GitCloneAndCheckoutCap, GitUrl};
-use std::collections::HashMap;
+use crate::{DownloadCapability, GitCloneAndCheckoutCap, GitListTagsCap, GitUrl};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::fs::File;
 use std::io::Write;
@@ -12,21 +14,40 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use url::{ParseError, Url};
 
-use crate::package::lock::{PackageLockSource, PackageLockSourceType};
+use crate::package::lock::{PackageLock, PackageLockSourceType};
 use crate::package::{
+    features::{self, Feature},
+    license::PackageLicense,
     lock::DependencyLock,
+    outdated,
+    registry::{self, RegistryEntry},
+    resolver::{self, DependencyProvider, Manifest},
+    sbom::{self, Component},
     target_properties::LibraryTargetProperties,
     tree::{DependencyTreeNode, GitLock, PackageDetails, ProjectSource},
-    ConfigFile, LIBRARY_DIRECTORY,
+    ConfigFile, LFC_INCLUDE_DIRECTORY, LIBRARY_DIRECTORY,
 };
 use crate::util::errors::LingoError;
 
 #[derive(Default)]
 pub struct DependencyManager {
-    /// queue of packages that need processing
-    pulling_queue: Vec<(String, PackageDetails)>,
     /// the flatten dependency tree with selected packages from the dependency tree
     lock: DependencyLock,
+    /// restrictions on how packages may be resolved and fetched
+    options: ResolutionOptions,
+    /// index used for dependencies that don't specify a source
+    registry: Option<Url>,
+    /// replacement sources from the `[patch]` table
+    patches: HashMap<String, PackageDetails>,
+}
+
+/// Restrictions that apply while resolving dependencies
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ResolutionOptions {
+    /// fail instead of modifying Lingo.lock
+    pub locked: bool,
+    /// never access the network, only use packages that are already available locally
+    pub offline: bool,
 }
 
 /// this copies all the files recursively from one location to another
@@ -45,33 +66,166 @@ pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Re
     Ok(())
 }
 
-impl TryFrom<&PackageLockSource> for PackageDetails {
+/// makes the directory available at `dst` without copying it, `dst` is replaced if it exists
+pub fn link_dir(src: &Path, dst: &Path) -> std::io::Result<()> {
+    remove_dir_or_link(dst)?;
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(src, dst);
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_dir(src, dst);
+    #[cfg(not(any(unix, windows)))]
+    copy_dir_all(src, dst)
+}
+
+/// removes a symlink without touching its target, directories are removed with their contents
+pub fn remove_dir_or_link(path: &Path) -> std::io::Result<()> {
+    match fs::symlink_metadata(path) {
+        // symlinks to directories are directories themselves on windows
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            fs::remove_file(path).or_else(|_| fs::remove_dir(path))
+        }
+        Ok(_) => fs::remove_dir_all(path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+impl TryFrom<&PackageLock> for PackageDetails {
     type Error = ParseError;
 
-    fn try_from(value: &PackageLockSource) -> Result<Self, Self::Error> {
-        let url = &value.uri;
+    fn try_from(value: &PackageLock) -> Result<Self, Self::Error> {
+        let url = &value.source.uri;
         Ok(PackageDetails {
-            version: Default::default(),
-            mutual_exclusive: match value.source_type {
+            // registry packages are looked up by their exact locked version
+            version: match value.source.source_type {
+                PackageLockSourceType::REGISTRY => registry::exact_requirement(&value.version),
+                _ => Default::default(),
+            },
+            mutual_exclusive: match value.source.source_type {
                 PackageLockSourceType::REGISTRY => {
-                    todo!()
+                    ProjectSource::Registry(Some(Url::from_str(url)?))
                 }
                 PackageLockSourceType::GIT => ProjectSource::Git(Url::from_str(url)?),
                 PackageLockSourceType::TARBALL => ProjectSource::TarBall(Url::from_str(url)?),
-                PackageLockSourceType::PATH => ProjectSource::Path(PathBuf::from(url)),
+                // older lock files quote the path
+                PackageLockSourceType::PATH => {
+                    ProjectSource::Path(PathBuf::from(url.trim_matches('"')))
+                }
             },
-            git_tag: value.rev.clone().map(GitLock::Rev),
-            git_rev: value.rev.clone(),
+            git_tag: value.source.rev.clone().map(GitLock::Rev),
+            git_rev: value.source.rev.clone(),
+            git_fetch: Default::default(),
+            subdir: value.subdir.clone(),
+            patched: value.patched,
+            sha256: value.sha256.clone(),
+            strip_prefix: value.strip_prefix.clone(),
+            features: value.features.iter().cloned().collect(),
+            optional: false,
+            default_features: false,
         })
     }
 }
 
+/// reads the file behind a `file://` url or downloads it otherwise
+pub fn download(url: &Url, download_cap: &DownloadCapability) -> anyhow::Result<Vec<u8>> {
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|()| anyhow::anyhow!("invalid file url {url}"))?;
+        Ok(fs::read(path)?)
+    } else {
+        Ok(download_cap(url)?)
+    }
+}
+
 impl PackageDetails {
+    /// returns true if fetching this package needs network access, git repositories can be
+    /// checked out from their cached mirror instead
+    pub fn requires_network(&self) -> bool {
+        match &self.mutual_exclusive {
+            ProjectSource::Git(_) => false,
+            ProjectSource::TarBall(url) => url.scheme() != "file",
+            ProjectSource::Registry(Some(index)) => index.scheme() != "file",
+            ProjectSource::Registry(None) => true,
+            ProjectSource::Path(_) => false,
+        }
+    }
+
+    /// replaces the source of the package with the one from the `[patch]` table, the
+    /// version requirement of the dependency is kept
+    pub fn with_patch(mut self, patch: Option<&PackageDetails>) -> Self {
+        if let Some(patch) = patch {
+            self.mutual_exclusive = patch.mutual_exclusive.clone();
+            self.git_tag = patch.git_tag.clone();
+            self.git_fetch = patch.git_fetch;
+            self.subdir = patch.subdir.clone();
+            self.sha256 = patch.sha256.clone();
+            self.strip_prefix = patch.strip_prefix.clone();
+            self.patched = true;
+        }
+        self
+    }
+
+    /// relative paths of path dependencies are interpreted relative to the directory of the
+    /// Lingo.toml that declares them
+    pub fn relative_to(mut self, base_path: &Path) -> Self {
+        if let ProjectSource::Path(path) = &self.mutual_exclusive {
+            if path.is_relative() {
+                self.mutual_exclusive = ProjectSource::Path(base_path.join(path));
+            }
+        }
+        self
+    }
+
+    /// packages without an explicit source are looked up in the default registry
+    pub fn with_default_registry(mut self, registry: Option<&Url>) -> Self {
+        if let ProjectSource::Registry(None) = self.mutual_exclusive {
+            self.mutual_exclusive = ProjectSource::Registry(registry.cloned());
+        }
+        self
+    }
+
+    /// downloads an archive, checks it against the expected sha256 and extracts it
+    fn fetch_archive(
+        &mut self,
+        url: &Url,
+        library_path: &Path,
+        download_cap: &DownloadCapability,
+    ) -> anyhow::Result<()> {
+        let archive = download(url, download_cap)?;
+        let digest = format!("{:x}", Sha256::digest(&archive));
+
+        if let Some(expected) = &self.sha256 {
+            if !expected.eq_ignore_ascii_case(&digest) {
+                return Err(LingoError::ArchiveChecksumMismatch(
+                    url.to_string(),
+                    expected.clone(),
+                    digest,
+                )
+                .into());
+            }
+        }
+        self.sha256 = Some(digest);
+
+        archive::extract(
+            &archive,
+            ArchiveFormat::from_url(url)?,
+            library_path,
+            self.strip_prefix.as_deref(),
+        )
+    }
+
     /// this function fetches the specified location and places it at the given location
     pub fn fetch(
         &mut self,
+        name: &str,
         library_path: &PathBuf,
         clone: &GitCloneAndCheckoutCap,
+        download_cap: &DownloadCapability,
     ) -> anyhow::Result<()> {
         match &self.mutual_exclusive {
             ProjectSource::Path(path_buf) => {
@@ -84,19 +238,343 @@ impl PackageDetails {
                     GitUrl::from(git_url.as_str()),
                     library_path,
                     self.git_tag.clone(),
+                    self.git_fetch,
+                    self.subdir.as_deref(),
                 )?;
                 Ok(())
             }
-            _ => Ok(()),
+            ProjectSource::TarBall(url) => {
+                let url = url.clone();
+                self.fetch_archive(&url, library_path, download_cap)
+            }
+            ProjectSource::Registry(Some(index)) => {
+                let index = index.clone();
+                let entries = registry::read_index(&index, name, download_cap)?;
+                let entry = registry::select(&entries, &self.version).ok_or_else(|| {
+                    LingoError::NoMatchingVersion(name.to_string(), self.version.to_string())
+                })?;
+
+                // a sha256 from the lock file takes precedence over the one in the index
+                if self.sha256.is_none() {
+                    self.sha256 = Some(entry.sha256.clone());
+                }
+                self.strip_prefix = entry.strip_prefix.clone();
+                self.fetch_archive(&entry.tarball_url(&index)?, library_path, download_cap)
+            }
+            ProjectSource::Registry(None) => {
+                Err(LingoError::NoRegistryConfigured(name.to_string()).into())
+            }
+        }
+    }
+}
+
+/// writes the lock file in the current format
+fn write_lock(lock: &DependencyLock, path: &Path) -> anyhow::Result<()> {
+    let mut lock_file = File::create(path)?;
+    let serialized_toml = toml::to_string(lock).expect("cannot generate toml");
+    lock_file.write_all(serialized_toml.as_ref())?;
+    Ok(())
+}
+
+/// directory inside the library folder where a fetched package is placed
+pub fn library_location(library_path: &Path, name: &str, version: &Versioning) -> PathBuf {
+    library_path.join(format!("{name}-{version}"))
+}
+
+/// reads the version of the package from its Lingo.toml
+fn read_version(package: &Path) -> anyhow::Result<Versioning> {
+    let lingo_toml_text = fs::read_to_string(package.join("Lingo.toml"))?;
+    Ok(toml::from_str::<ConfigFile>(&lingo_toml_text)?
+        .package
+        .version)
+}
+
+/// parses a git tag like `v1.2.0` or `1.2.0` into a version
+pub(crate) fn version_from_tag(tag: &str) -> Option<Versioning> {
+    match Versioning::new(tag.strip_prefix('v').unwrap_or(tag)) {
+        Some(version @ (Versioning::Ideal(_) | Versioning::General(_))) => Some(version),
+        _ => None,
+    }
+}
+
+/// A package inside the library folder together with its declared dependencies and features
+struct FetchedPackage {
+    node: DependencyTreeNode,
+    manifest: Manifest,
+    features: HashMap<String, Feature>,
+}
+
+/// Identifies a package by its name and where it is taken from, so packages of the same name
+/// from different sources don't share fetched versions.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PackageId {
+    name: String,
+    source: ProjectSource,
+    git_tag: Option<GitLock>,
+    subdir: Option<PathBuf>,
+}
+
+impl PackageId {
+    fn new(name: &str, details: &PackageDetails) -> Self {
+        PackageId {
+            name: name.to_string(),
+            source: details.mutual_exclusive.clone(),
+            git_tag: details.git_tag.clone(),
+            subdir: details.subdir.clone(),
+        }
+    }
+}
+
+/// Fetches packages on demand while the resolver explores the dependency graph.
+struct PackageFetcher<'a> {
+    /// directory where fetched packages are placed
+    library_path: PathBuf,
+    /// restrictions on how packages may be fetched
+    options: ResolutionOptions,
+    /// index used for dependencies that don't specify a source
+    registry: Option<Url>,
+    /// replacement sources from the `[patch]` table
+    patches: HashMap<String, PackageDetails>,
+    clone: &'a GitCloneAndCheckoutCap<'a>,
+    download: &'a DownloadCapability<'a>,
+    list_tags: &'a GitListTagsCap<'a>,
+    /// packages that have been fetched into the library folder
+    fetched: HashMap<(PackageId, Versioning), FetchedPackage>,
+    /// version of packages whose source provides exactly one version
+    pinned: HashMap<PackageId, Versioning>,
+    /// version tags of git repositories
+    tags: HashMap<Url, Vec<(Versioning, String)>>,
+    /// published versions of packages in a registry
+    published: HashMap<(Url, String), Vec<RegistryEntry>>,
+}
+
+impl<'a> PackageFetcher<'a> {
+    /// fetches the package into the library folder and returns its version
+    fn fetch(&mut self, id: &PackageId, mut package: PackageDetails) -> anyhow::Result<Versioning> {
+        let name = id.name.as_str();
+        if self.options.offline && package.requires_network() {
+            return Err(LingoError::OfflineFetch(name.to_string()).into());
+        }
+        package.git_fetch.offline = self.options.offline;
+
+        eprintln!("{} {}", "Fetching".green().bold(), name);
+
+        let location = match &package.mutual_exclusive {
+            // path dependencies are used in place, so changes show up in the next build
+            ProjectSource::Path(path) => {
+                let location = fs::canonicalize(path).map_err(|e| {
+                    anyhow::anyhow!("cannot find {name} at {}: {e}", path.display())
+                })?;
+                package.mutual_exclusive = ProjectSource::Path(location.clone());
+                location
+            }
+            _ => {
+                // place where to drop the source
+                let temporary = tempfile::tempdir_in(&self.library_path)?;
+                package.fetch(
+                    name,
+                    &temporary.path().to_path_buf(),
+                    self.clone,
+                    self.download,
+                )?;
+                let version = read_version(temporary.path())?;
+
+                // moving the package to its final location
+                let location = library_location(&self.library_path, name, &version);
+                let _ = fs::remove_dir_all(&location);
+                fs::rename(temporary.keep(), &location)?;
+                location
+            }
+        };
+
+        let hash = sha1dir::checksum_dir(&location, false)?;
+
+        let version = read_version(&location)?;
+        log::debug!("fetched {name} {version} into {}", location.display());
+
+        let lingo_toml_text = fs::read_to_string(location.join("Lingo.toml"))?;
+        let read_toml = toml::from_str::<ConfigFile>(&lingo_toml_text)?.to_config(&location)?;
+        let library = match read_toml.library {
+            Some(value) => value,
+            None => {
+                // error we expected a library here
+                return Err(
+                    LingoError::NoLibraryInLingoToml(location.display().to_string()).into(),
+                );
+            }
+        };
+
+        let node = DependencyTreeNode {
+            name: name.to_string(),
+            package,
+            location,
+            include_path: library.location.clone(),
+            dependencies: vec![],
+            requirements: read_toml
+                .dependencies
+                .iter()
+                .map(|(name, details)| (name.clone(), details.version.clone()))
+                .collect(),
+            hash: hash.to_string(),
+            version: version.clone(),
+            features: Default::default(),
+            license: read_toml.package.license.clone(),
+            properties: library.properties,
+        };
+        let manifest = Manifest {
+            dependencies: read_toml
+                .dependencies
+                .into_iter()
+                .map(|(name, details)| {
+                    let details = details
+                        .with_patch(self.patches.get(&name))
+                        .with_default_registry(self.registry.as_ref());
+                    (name, details)
+                })
+                .collect(),
+            features: read_toml
+                .features
+                .iter()
+                .map(|(name, feature)| (name.clone(), feature.enables.clone()))
+                .collect(),
+        };
+
+        self.fetched.insert(
+            (id.clone(), version.clone()),
+            FetchedPackage {
+                node,
+                manifest,
+                features: read_toml.features,
+            },
+        );
+        Ok(version)
+    }
+
+    /// lists the tags of the repository that look like versions
+    fn git_tags(&mut self, url: &Url) -> anyhow::Result<Vec<(Versioning, String)>> {
+        if let Some(tags) = self.tags.get(url) {
+            return Ok(tags.clone());
+        }
+        let tags: Vec<(Versioning, String)> =
+            (self.list_tags)(GitUrl::from(url.as_str()), self.options.offline)?
+                .into_iter()
+                .filter_map(|tag| version_from_tag(&tag).map(|version| (version, tag)))
+                .collect();
+        self.tags.insert(url.clone(), tags.clone());
+        Ok(tags)
+    }
+
+    /// reads the published versions of the package from the registry
+    fn registry_entries(&mut self, name: &str, index: &Url) -> anyhow::Result<Vec<RegistryEntry>> {
+        let key = (index.clone(), name.to_string());
+        if let Some(entries) = self.published.get(&key) {
+            return Ok(entries.clone());
+        }
+        if self.options.offline && index.scheme() != "file" {
+            return Err(LingoError::OfflineFetch(name.to_string()).into());
+        }
+
+        let entries = registry::read_index(index, name, self.download)?;
+        self.published.insert(key, entries.clone());
+        Ok(entries)
+    }
+
+    /// fetches a source that provides exactly one version
+    fn pinned_version(
+        &mut self,
+        name: &str,
+        source: &PackageDetails,
+    ) -> anyhow::Result<Versioning> {
+        let id = PackageId::new(name, source);
+        if let Some(version) = self.pinned.get(&id) {
+            return Ok(version.clone());
+        }
+        let version = self.fetch(&id, source.clone())?;
+        self.pinned.insert(id, version.clone());
+        Ok(version)
+    }
+}
+
+impl<'a> DependencyProvider for PackageFetcher<'a> {
+    fn available_versions(
+        &mut self,
+        name: &str,
+        source: &PackageDetails,
+    ) -> anyhow::Result<Vec<Versioning>> {
+        match &source.mutual_exclusive {
+            ProjectSource::Registry(Some(index)) => Ok(self
+                .registry_entries(name, index)?
+                .into_iter()
+                .map(|entry| entry.version)
+                .collect()),
+            ProjectSource::Registry(None) => {
+                Err(LingoError::NoRegistryConfigured(name.to_string()).into())
+            }
+            // without a branch, tag or revision every version tag is a candidate
+            ProjectSource::Git(url) if source.git_tag.is_none() => {
+                let tags = self.git_tags(url)?;
+                if tags.is_empty() {
+                    // repositories without version tags only provide their default branch
+                    Ok(vec![self.pinned_version(name, source)?])
+                } else {
+                    Ok(tags.into_iter().map(|(version, _)| version).collect())
+                }
+            }
+            _ => Ok(vec![self.pinned_version(name, source)?]),
         }
     }
+
+    fn manifest(
+        &mut self,
+        name: &str,
+        source: &PackageDetails,
+        version: &Versioning,
+    ) -> anyhow::Result<Manifest> {
+        let key = (PackageId::new(name, source), version.clone());
+        if !self.fetched.contains_key(&key) {
+            let mut candidate = source.clone();
+            match &source.mutual_exclusive {
+                ProjectSource::Registry(_) => {
+                    candidate.version = registry::exact_requirement(version);
+                }
+                ProjectSource::Git(url) => {
+                    let tag = self
+                        .git_tags(url)?
+                        .into_iter()
+                        .find(|(tagged, _)| tagged == version)
+                        .map(|(_, tag)| tag);
+                    candidate.git_tag = tag.map(GitLock::Tag);
+                }
+                _ => {}
+            }
+
+            let fetched = self.fetch(&key.0, candidate)?;
+            if &fetched != version {
+                error!(
+                    "version mismatch between specified location and requested version requirement"
+                );
+                return Err(LingoError::LingoVersionMismatch(format!(
+                    "expected {name} {version} got version {fetched}"
+                ))
+                .into());
+            }
+        }
+
+        Ok(self.fetched[&key].manifest.clone())
+    }
 }
 
 impl DependencyManager {
+    #[allow(clippy::too_many_arguments)] // the capabilities are injected by the binary
     pub fn from_dependencies(
         dependencies: Vec<(String, PackageDetails)>,
         target_path: &Path,
+        options: ResolutionOptions,
+        registry: Option<&Url>,
+        patches: &HashMap<String, PackageDetails>,
         git_clone_and_checkout_cap: &GitCloneAndCheckoutCap,
+        download_cap: &DownloadCapability,
+        git_list_tags_cap: &GitListTagsCap,
     ) -> anyhow::Result<DependencyManager> {
         // create library folder
         let library_path = target_path.join(LIBRARY_DIRECTORY);
@@ -109,47 +587,98 @@ impl DependencyManager {
         // checks if a Lingo.lock file exists
         if lock_file.exists() {
             // reads and parses Lockfile
-            lock = toml::from_str::<DependencyLock>(&fs::read_to_string(lock_file)?)
-                .expect("cannot parse lock");
-
-            // if a lock file is present it will load the dependencies from it and checks
-            // integrity of the build directory
-            if let Ok(()) = lock.init(&target_path.join("lfc_include"), git_clone_and_checkout_cap)
-            {
-                return Ok(DependencyManager {
-                    pulling_queue: vec![],
-                    lock,
-                });
+            lock = DependencyLock::read(&lock_file)?;
+
+            if lock.needs_migration() && options.locked {
+                return Err(LingoError::LockFileOutdated(
+                    "Lingo.lock uses an older format and has to be migrated".to_string(),
+                )
+                .into());
             }
+
+            // checks that the lock file still matches the requirements from the Lingo.toml
+            if let Err(e) = lock.satisfies(&dependencies, patches) {
+                if options.locked {
+                    return Err(LingoError::LockFileOutdated(e.to_string()).into());
+                }
+                warn!("Lingo.lock is outdated ({e}), resolving dependencies again");
+            } else {
+                // if a lock file is present it will load the dependencies from it and checks
+                // integrity of the build directory
+                match lock.init(
+                    &target_path.join(LFC_INCLUDE_DIRECTORY),
+                    &library_path,
+                    options.offline,
+                    git_clone_and_checkout_cap,
+                    download_cap,
+                ) {
+                    Ok(updated) => {
+                        if !updated.is_empty() && options.locked {
+                            return Err(LingoError::LockFileOutdated(format!(
+                                "path dependencies were modified: {}",
+                                updated.join(", ")
+                            ))
+                            .into());
+                        }
+                        if lock.needs_migration() || !updated.is_empty() {
+                            write_lock(&lock, &lock_file)?;
+                        }
+                        return Ok(DependencyManager {
+                            lock,
+                            options,
+                            registry: registry.cloned(),
+                            patches: patches.clone(),
+                        });
+                    }
+                    Err(e) if options.locked => return Err(e),
+                    // the fetched packages don't match the lock, resolving again would hide this
+                    Err(e)
+                        if matches!(
+                            e.downcast_ref::<LingoError>(),
+                            Some(LingoError::ChecksumMismatch(..))
+                        ) =>
+                    {
+                        return Err(e)
+                    }
+                    Err(e) => {
+                        warn!(
+                            "cannot load dependencies from Lingo.lock ({e}), resolving them again"
+                        );
+                    }
+                }
+            }
+        } else if options.locked {
+            return Err(
+                LingoError::LockFileOutdated("Lingo.lock does not exist".to_string()).into(),
+            );
         }
 
         // creates a new dependency manager object
-        manager = DependencyManager::default();
+        manager = DependencyManager {
+            options,
+            registry: registry.cloned(),
+            patches: patches.clone(),
+            ..Default::default()
+        };
 
-        // starts recursively pulling dependencies
-        let root_nodes = manager.pull(
+        // resolves the dependency graph and fetches the selected packages
+        let selection = manager.pull(
             dependencies.clone(),
             target_path,
             git_clone_and_checkout_cap,
+            download_cap,
+            git_list_tags_cap,
         )?;
 
-        // flattens the dependency tree and makes the package selection
-        let selection = DependencyManager::flatten(root_nodes.clone())?;
-
         // creates a lock file struct from the selected packages
-        lock = DependencyLock::create(selection);
+        lock = DependencyLock::create(selection, &fs::canonicalize(target_path.join(".."))?);
 
         // writes the lock file down
-        let mut lock_file = File::create(target_path.join("../Lingo.lock"))?;
-
-        println!("{:?}", lock.dependencies);
-        let serialized_toml = toml::to_string(&lock).expect("cannot generate toml");
-
-        lock_file.write_all(serialized_toml.as_ref())?;
+        write_lock(&lock, &target_path.join("../Lingo.lock"))?;
 
         // moves the selected packages into the include folder
-        let include_folder = target_path.join("lfc_include");
-        lock.create_library_folder(&library_path, &include_folder)
+        let include_folder = target_path.join(LFC_INCLUDE_DIRECTORY);
+        lock.create_library_folder(&include_folder)
             .expect("creating lock folder failed");
 
         // saves the lockfile with the dependency manager
@@ -158,199 +687,302 @@ impl DependencyManager {
         Ok(manager)
     }
 
-    pub fn pull(
-        &mut self,
-        mut dependencies: Vec<(String, PackageDetails)>,
-        root_path: &Path,
+    /// Checks the fetched packages against Lingo.lock, see [`DependencyLock::verify`]
+    pub fn verify(
+        target_path: &Path,
+        repair: bool,
         git_clone_and_checkout_cap: &GitCloneAndCheckoutCap,
-    ) -> anyhow::Result<Vec<DependencyTreeNode>> {
-        let mut sub_dependencies = vec![];
-        self.pulling_queue.append(&mut dependencies);
-        let sub_dependency_path = root_path.join("libraries");
-        //fs::remove_dir_all(&sub_dependency_path)?;
-        fs::create_dir_all(&sub_dependency_path)?;
-
-        while !self.pulling_queue.is_empty() {
-            if let Some((package_name, package_details)) = self.pulling_queue.pop() {
-                print!("{} {} ...", "Cloning".green().bold(), package_name);
-                let node = match self.non_recursive_fetching(
-                    &package_name,
-                    package_details,
-                    &sub_dependency_path,
-                    git_clone_and_checkout_cap,
-                ) {
-                    Ok(value) => value,
-                    Err(e) => {
-                        return Err(e);
-                    }
-                };
-
-                sub_dependencies.push(node);
-            } else {
-                break;
-            }
+        download_cap: &DownloadCapability,
+    ) -> anyhow::Result<()> {
+        let lock_file = target_path.join("../Lingo.lock");
+        if !lock_file.exists() {
+            return Err(anyhow::anyhow!("Lingo.lock does not exist"));
         }
 
-        //dependencies
-        Ok(sub_dependencies)
+        let lock = DependencyLock::read(&lock_file)?;
+        lock.verify(
+            &target_path.join(LFC_INCLUDE_DIRECTORY),
+            &target_path.join(LIBRARY_DIRECTORY),
+            repair,
+            git_clone_and_checkout_cap,
+            download_cap,
+        )
     }
 
-    pub(crate) fn non_recursive_fetching(
-        &mut self,
-        name: &str,
-        mut package: PackageDetails,
-        base_path: &Path,
-        git_clone_and_checkout_cap: &GitCloneAndCheckoutCap,
-    ) -> anyhow::Result<DependencyTreeNode> {
-        // creating the directory where the library will be housed
-        let library_path = base_path; //.join("libs");
-                                      // place where to drop the source
-        let temporary_path = library_path.join("temporary");
-        let _ = fs::remove_dir_all(&temporary_path);
-        let _ = fs::create_dir_all(&temporary_path);
-
-        // directory where the dependencies will be dropped
-
-        // creating the necessary directories
-        fs::create_dir_all(library_path)?;
-        fs::create_dir_all(&temporary_path)?;
-
-        // cloning the specified package
-        package.fetch(&temporary_path, git_clone_and_checkout_cap)?;
-
-        let hash = sha1dir::checksum_current_dir(&temporary_path, false);
-        let include_path = library_path.join(hash.to_string());
-
-        let lingo_toml_text = fs::read_to_string(temporary_path.clone().join("Lingo.toml"))?;
-        let read_toml = toml::from_str::<ConfigFile>(&lingo_toml_text)?.to_config(&temporary_path);
-
-        println!(" {}", read_toml.package.version);
-
-        let config = match read_toml.library {
-            Some(value) => value,
-            None => {
-                // error we expected a library here
-                return Err(
-                    LingoError::NoLibraryInLingoToml(library_path.display().to_string()).into(),
-                );
-            }
-        };
-
-        if !package.version.matches(&read_toml.package.version) {
-            error!("version mismatch between specified location and requested version requirement");
-            return Err(LingoError::LingoVersionMismatch(format!(
-                "requested version {} got version {}",
-                package.version, read_toml.package.version
-            ))
-            .into());
-        }
-
-        let dependencies = vec![];
-
-        for dep in read_toml.dependencies {
-            self.pulling_queue.push(dep);
+    /// Prints the locked version, the newest version allowed by the requirements and the newest
+    /// available version of every locked package
+    pub fn outdated(
+        dependencies: &[(String, PackageDetails)],
+        target_path: &Path,
+        git_list_tags_cap: &GitListTagsCap,
+        download_cap: &DownloadCapability,
+    ) -> anyhow::Result<()> {
+        let lock_file = target_path.join("../Lingo.lock");
+        if !lock_file.exists() {
+            return Err(anyhow::anyhow!("Lingo.lock does not exist"));
         }
 
-        fs::create_dir_all(&include_path)?;
-        copy_dir_all(&temporary_path, &include_path)?;
-
-        Ok(DependencyTreeNode {
-            name: name.to_string(),
-            package: package.clone(),
-            location: include_path.clone(),
-            include_path: config.location.clone(),
-            dependencies: dependencies.clone(),
-            hash: hash.to_string(),
-            version: read_toml.package.version.clone(),
-            properties: config.properties,
-        })
+        let lock = DependencyLock::read(&lock_file)?;
+        outdated::print(&outdated::check(
+            &lock,
+            dependencies,
+            git_list_tags_cap,
+            download_cap,
+        )?);
+        Ok(())
     }
 
-    fn flatten(root_nodes: Vec<DependencyTreeNode>) -> anyhow::Result<Vec<DependencyTreeNode>> {
-        // implementation idea:
-        // 1.   we collect all the version requirements for packages => are the different
-        //      constraints satisfiable ?
-        // 2.   we collect all the different sources
-        // 3.   finding the set of sources that satisfies the set of version constraints
-        // 4.   pick the newest version from that set
-        // TODO: later we can probably do this in one pass
-
-        let mut constraints = HashMap::<&String, Vec<Requirement>>::new();
-        let mut sources = HashMap::<&String, Vec<&DependencyTreeNode>>::new();
-
-        // this basically flattens the
-        let mut nodes = Vec::new();
-        for node in root_nodes {
-            let mut children = node.aggregate();
-            nodes.append(&mut children);
-        }
-
-        for node in &nodes {
-            let constraint = &node.package.version;
-
-            constraints
-                .entry(&node.name)
-                .and_modify(|value| {
-                    value.push(constraint.clone());
-                })
-                .or_insert(vec![constraint.clone()]);
+    /// Resolves the versions of all direct and transitive dependencies and returns the
+    /// selected packages. Packages are fetched into the library folder as needed.
+    pub fn pull(
+        &mut self,
+        dependencies: Vec<(String, PackageDetails)>,
+        root_path: &Path,
+        git_clone_and_checkout_cap: &GitCloneAndCheckoutCap,
+        download_cap: &DownloadCapability,
+        git_list_tags_cap: &GitListTagsCap,
+    ) -> anyhow::Result<Vec<DependencyTreeNode>> {
+        let library_path = root_path.join(LIBRARY_DIRECTORY);
+        fs::create_dir_all(&library_path)?;
 
-            sources
-                .entry(&node.name)
-                .and_modify(move |value| {
-                    value.push(node);
-                })
-                .or_insert(vec![&node]);
-        }
+        let mut fetcher = PackageFetcher {
+            library_path,
+            options: self.options,
+            registry: self.registry.clone(),
+            patches: self.patches.clone(),
+            clone: git_clone_and_checkout_cap,
+            download: download_cap,
+            list_tags: git_list_tags_cap,
+            fetched: HashMap::new(),
+            pinned: HashMap::new(),
+            tags: HashMap::new(),
+            published: HashMap::new(),
+        };
 
-        let merged: Vec<(&String, Vec<Requirement>, Vec<&DependencyTreeNode>)> = constraints
+        // sorting makes the resolution independent of the order in the Lingo.toml
+        let mut dependencies: Vec<(String, PackageDetails)> = dependencies
             .into_iter()
-            .filter_map(move |(key, requirements)| {
-                sources
-                    .get_mut(&key)
-                    .map(move |location| (key, requirements, location.clone()))
+            .map(|(name, details)| {
+                let details = details
+                    .with_patch(self.patches.get(&name))
+                    .with_default_registry(self.registry.as_ref());
+                (name, details)
             })
             .collect();
+        dependencies.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-        let mut selection = Vec::new();
-
-        for (_, requirements, location) in merged {
-            //TODO: replace this in the future by first merging all the requirements
-            // (determine upper and lower bound)
-
-            let mut filtered_results: Vec<&DependencyTreeNode> = location
-                .into_iter()
-                .filter(|location| {
-                    let filter = |version: &Versioning| {
-                        for requirement in &requirements {
-                            if !requirement.matches(version) {
-                                return false;
-                            }
-                        }
-                        true
-                    };
-
-                    filter(&location.version)
-                })
-                .collect();
+        let resolution = resolver::resolve(dependencies, &mut fetcher)?;
 
-            if filtered_results.is_empty() {
-                error!("no viable package was found that fulfills all the requirements");
-            }
-
-            filtered_results.sort_by_key(|value| value.version.clone());
-
-            let package = filtered_results
-                .last()
-                .expect("There should be at least one viable package remaining!");
+        resolution
+            .into_iter()
+            .map(|package| {
+                let FetchedPackage {
+                    mut node, features, ..
+                } = fetcher
+                    .fetched
+                    .remove(&(
+                        PackageId::new(&package.name, &package.source),
+                        package.version,
+                    ))
+                    .expect("resolved packages have been fetched");
+
+                // optional dependencies that are not enabled aren't part of the lock
+                node.requirements
+                    .retain(|name, _| package.dependencies.contains(name));
+                features::apply(&mut node.properties, &features, &package.features)?;
+                node.features = package.features;
+                Ok(node)
+            })
+            .collect()
+    }
 
-            selection.push((*package).clone());
-        }
+    /// license of every package in the dependency graph
+    pub fn licenses(&self) -> Vec<PackageLicense> {
+        self.lock.licenses()
+    }
 
-        Ok(selection)
+    /// every package in the dependency graph as it is listed in the bill of materials
+    pub fn components(&self) -> Vec<Component> {
+        sbom::components(&self.lock, &self.licenses())
     }
 
     pub fn get_target_properties(&self) -> anyhow::Result<LibraryTargetProperties> {
         self.lock.aggregate_target_properties()
     }
+
+    /// include directory of every library inside `target_path`, see [`DependencyLock::include_paths`]
+    pub fn include_paths(&self, target_path: &Path) -> anyhow::Result<BTreeMap<String, PathBuf>> {
+        self.lock
+            .include_paths(&target_path.join(LFC_INCLUDE_DIRECTORY))
+    }
+}
+
+#[test]
+fn test_migrate_unversioned_lock_file() {
+    use crate::package::lock::write_test_library;
+    use std::cell::RefCell;
+
+    let project = tempfile::tempdir().unwrap();
+    let target_path = project.path().join("build");
+    let lock_file = project.path().join("Lingo.lock");
+
+    // lock file and library cache written by a lingo without lock file versions
+    let legacy = r#"
+[alpha]
+name = "alpha"
+version = "1.0.0"
+source = "git+https://example.com/alpha.git#0123abcd"
+checksum = "5e2a7c6f"
+
+[beta]
+name = "beta"
+version = "0.1.0"
+source = "git+https://example.com/beta.git#4567cdef"
+checksum = "9b1d04e3"
+"#;
+    fs::write(&lock_file, legacy).unwrap();
+    write_test_library(
+        &target_path.join(LIBRARY_DIRECTORY).join("5e2a7c6f"),
+        "alpha",
+        "1.0.0",
+        "beta = { version = \">=0.1.0\", git = \"https://example.com/beta.git\" }\n",
+        "target C\n",
+    );
+
+    let clones = RefCell::new(Vec::new());
+    let clone: GitCloneAndCheckoutCap = Box::new(|url, location, _, _, _| {
+        let url: &str = url.into();
+        clones.borrow_mut().push(url.to_string());
+        write_test_library(location, "beta", "0.1.0", "", "target C\n");
+        Ok(Some("4567cdef".to_string()))
+    });
+    let download: DownloadCapability =
+        Box::new(|url| Err(crate::DownloadError::NotFound(url.to_string())));
+    let list_tags: GitListTagsCap = Box::new(|_, _| Ok(vec![]));
+    let dependencies = vec![(
+        "alpha".to_string(),
+        toml::from_str::<PackageDetails>(
+            "version = \">=1.0.0\"\ngit = \"https://example.com/alpha.git\"",
+        )
+        .unwrap(),
+    )];
+    let load = |locked| {
+        DependencyManager::from_dependencies(
+            dependencies.clone(),
+            &target_path,
+            ResolutionOptions {
+                locked,
+                offline: false,
+            },
+            None,
+            &HashMap::new(),
+            &clone,
+            &download,
+            &list_tags,
+        )
+    };
+
+    // migrating modifies the lock file
+    let error = load(true).err().unwrap();
+    assert!(matches!(
+        error.downcast_ref::<LingoError>(),
+        Some(LingoError::LockFileOutdated(_))
+    ));
+    assert_eq!(fs::read_to_string(&lock_file).unwrap(), legacy);
+
+    load(false).unwrap();
+    assert_eq!(*clones.borrow(), ["https://example.com/beta.git"]);
+
+    let lock = DependencyLock::read(&lock_file).unwrap();
+    assert!(!lock.needs_migration());
+    let alpha = &lock.dependencies["alpha"];
+    let cache = library_location(
+        &target_path.join(LIBRARY_DIRECTORY),
+        "alpha",
+        &alpha.version,
+    );
+    assert_eq!(
+        alpha.checksum,
+        sha1dir::checksum_dir(&cache, false).unwrap().to_string()
+    );
+    assert!(alpha.dependencies.contains_key("beta"));
+    assert!(!target_path
+        .join(LIBRARY_DIRECTORY)
+        .join("5e2a7c6f")
+        .exists());
+
+    // the migrated lock is used as is
+    load(true).unwrap();
+    assert_eq!(clones.borrow().len(), 1);
+}
+
+#[test]
+fn test_patch_replaces_transitive_sources() {
+    use crate::package::lock::write_test_library;
+    use std::cell::RefCell;
+
+    let project = tempfile::tempdir().unwrap();
+    let target_path = project.path().join("build");
+    write_test_library(
+        &project.path().join("beta"),
+        "beta",
+        "0.2.0",
+        "",
+        "target C\n",
+    );
+
+    let clones = RefCell::new(Vec::new());
+    let clone: GitCloneAndCheckoutCap = Box::new(|url, location, _, _, _| {
+        let url: &str = url.into();
+        clones.borrow_mut().push(url.to_string());
+        write_test_library(
+            location,
+            "alpha",
+            "1.0.0",
+            "beta = { version = \">=0.1.0\", git = \"https://example.com/beta.git\" }\n",
+            "target C\n",
+        );
+        Ok(Some("0123abcd".to_string()))
+    });
+    let download: DownloadCapability =
+        Box::new(|url| Err(crate::DownloadError::NotFound(url.to_string())));
+    let list_tags: GitListTagsCap = Box::new(|_, _| Ok(vec![]));
+    let dependencies = vec![(
+        "alpha".to_string(),
+        toml::from_str::<PackageDetails>(
+            "version = \">=1.0.0\"\ngit = \"https://example.com/alpha.git\"",
+        )
+        .unwrap(),
+    )];
+    let patch =
+        toml::from_str::<PackageDetails>(&format!("path = {:?}", project.path().join("beta")))
+            .unwrap();
+    let load = |patches: &HashMap<String, PackageDetails>, locked| {
+        DependencyManager::from_dependencies(
+            dependencies.clone(),
+            &target_path,
+            ResolutionOptions {
+                locked,
+                offline: false,
+            },
+            None,
+            patches,
+            &clone,
+            &download,
+            &list_tags,
+        )
+    };
+
+    // the dependency of alpha is taken from the patch instead of its repository
+    load(&HashMap::from([("beta".to_string(), patch)]), false).unwrap();
+    assert_eq!(*clones.borrow(), ["https://example.com/alpha.git"]);
+    let lock = DependencyLock::read(&project.path().join("Lingo.lock")).unwrap();
+    let beta = &lock.dependencies["beta"];
+    assert!(beta.patched);
+    assert_eq!(beta.source.source_type, PackageLockSourceType::PATH);
+    assert_eq!(beta.version.to_string(), "0.2.0");
+
+    // removing the patch invalidates the lock
+    let error = load(&HashMap::new(), true).err().unwrap();
+    assert!(error.to_string().contains("patch for beta was removed"));
 }
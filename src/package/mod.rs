@@ -1,5 +1,11 @@
+pub mod features;
+pub mod license;
 pub mod lock;
 pub mod management;
+pub mod outdated;
+pub mod registry;
+pub mod resolver;
+pub mod sbom;
 pub mod tree;
 
 pub mod target_properties;
@@ -7,8 +13,9 @@ pub mod target_properties;
 use serde::de::{Error, Visitor};
 use serde::{Deserializer, Serializer};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use tempfile::tempdir;
+use url::Url;
 use versions::Versioning;
 
 use std::fs::{remove_dir_all, remove_file, write};
@@ -25,9 +32,12 @@ use crate::args::{
 };
 use crate::package::tree::GitLock;
 use crate::package::{
+    features::{Activation, Feature, FeatureFile, FeatureRequest},
+    license::Policy,
+    registry::RegistryFile,
     target_properties::{
         AppTargetProperties, AppTargetPropertiesFile, LibraryTargetProperties,
-        LibraryTargetPropertiesFile,
+        LibraryTargetPropertiesFile, MergeTargetProperties,
     },
     tree::PackageDetails,
 };
@@ -42,6 +52,8 @@ pub const OUTPUT_DIRECTORY: &str = "build";
 /// name of the folder inside the `OUTPUT_DIRECTORY` where libraries
 /// will be loaded (cloned, extracted, copied) into for further processing.
 pub const LIBRARY_DIRECTORY: &str = "libraries";
+/// name of the folder inside the `OUTPUT_DIRECTORY` where lfc finds the libraries
+pub const LFC_INCLUDE_DIRECTORY: &str = "lfc_include";
 
 /// default folder for lf executable files
 const DEFAULT_EXECUTABLE_FOLDER: &str = "src";
@@ -77,6 +89,21 @@ pub struct ConfigFile {
 
     /// Dependencies for required to build this Lingua-Franca Project
     pub dependencies: HashMap<String, PackageDetails>,
+
+    /// package index for dependencies that only specify a version
+    pub registry: Option<RegistryFile>,
+
+    /// replacement sources for packages anywhere in the dependency graph
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub patch: HashMap<String, PackageDetails>,
+
+    /// optional parts of this package
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub features: HashMap<String, FeatureFile>,
+
+    /// rules the dependencies have to follow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Policy>,
 }
 
 /// This struct is used after filling in all the defaults
@@ -93,6 +120,54 @@ pub struct Config {
 
     /// Dependencies for required to build this Lingua-Franca Project
     pub dependencies: HashMap<String, PackageDetails>,
+
+    /// url of the package index for dependencies that only specify a version
+    pub registry: Option<Url>,
+
+    /// replacement sources for packages anywhere in the dependency graph
+    pub patches: HashMap<String, PackageDetails>,
+
+    /// optional parts of this package
+    pub features: HashMap<String, Feature>,
+
+    /// rules the dependencies have to follow
+    pub policy: Policy,
+}
+
+impl Config {
+    /// enables the requested features of this package
+    fn activate(&self, request: &FeatureRequest) -> anyhow::Result<Activation> {
+        let dependencies = Vec::from_iter(self.dependencies.clone());
+        let enables = self
+            .features
+            .iter()
+            .map(|(name, feature)| (name.clone(), feature.enables.clone()))
+            .collect();
+        features::activate(&self.package.name, &dependencies, &enables, request)
+    }
+
+    /// the dependencies that are used with the requested features of this package enabled
+    pub fn active_dependencies(
+        &self,
+        request: &FeatureRequest,
+    ) -> anyhow::Result<Vec<(String, PackageDetails)>> {
+        Ok(self.activate(request)?.dependencies)
+    }
+
+    /// Enables the requested features of this package, the target properties of the enabled
+    /// features are added to every app. Returns the dependencies that are used.
+    pub fn enable_features(
+        &mut self,
+        request: &FeatureRequest,
+    ) -> anyhow::Result<Vec<(String, PackageDetails)>> {
+        let activation = self.activate(request)?;
+        let mut properties = LibraryTargetProperties::default();
+        features::apply(&mut properties, &self.features, &activation.features)?;
+        for app in &mut self.apps {
+            app.properties.merge(&properties)?;
+        }
+        Ok(activation.dependencies)
+    }
 }
 
 /// The Format inside the Lingo.toml under [lib]
@@ -150,10 +225,83 @@ pub struct AppFile {
     /// platform of this project
     pub platform: Option<Platform>,
 
+    /// board the app is built for on embedded platforms, like `nrf52dk/nrf52832`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub board: Option<String>,
+
+    /// compilers and flags the app is cross-compiled with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolchain: Option<Toolchain>,
+
     /// target properties of that lingua-franca app
     pub properties: AppTargetPropertiesFile,
 }
 
+/// The toolchain an app is compiled with, under [app.toolchain]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Toolchain {
+    /// target triple like `aarch64-linux-gnu`, also names the build directory. It is only passed
+    /// to clang, other compilers are built for a single target and are chosen with `cc`/`cxx`
+    pub triple: Option<String>,
+    /// CMake toolchain file
+    pub file: Option<PathBuf>,
+    /// C compiler
+    pub cc: Option<String>,
+    /// C++ compiler
+    pub cxx: Option<String>,
+    /// root directory of the headers and libraries of the target
+    pub sysroot: Option<PathBuf>,
+    /// additional flags passed to the C and C++ compiler
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+}
+
+impl Toolchain {
+    /// toolchain that only consists of a CMake toolchain file
+    pub fn from_file(file: PathBuf) -> Toolchain {
+        Toolchain {
+            file: Some(file),
+            ..Default::default()
+        }
+    }
+
+    /// the fields set in `other` take precedence over the ones of this toolchain
+    pub fn merge(self, other: Toolchain) -> Toolchain {
+        Toolchain {
+            triple: other.triple.or(self.triple),
+            file: other.file.or(self.file),
+            cc: other.cc.or(self.cc),
+            cxx: other.cxx.or(self.cxx),
+            sysroot: other.sysroot.or(self.sysroot),
+            flags: if other.flags.is_empty() {
+                self.flags
+            } else {
+                other.flags
+            },
+        }
+    }
+
+    /// resolves the paths against the package directory
+    fn from(self, path: &Path) -> Toolchain {
+        Toolchain {
+            file: self.file.map(|file| path.join(file)),
+            sysroot: self.sysroot.map(|sysroot| path.join(sysroot)),
+            ..self
+        }
+    }
+
+    /// name of the build directory, the triple or otherwise the name of the toolchain file
+    pub fn name(&self) -> Option<String> {
+        self.triple.clone().or_else(|| {
+            self.file
+                .as_ref()
+                .and_then(|file| file.file_stem())
+                .map(|stem| stem.to_string_lossy().to_string())
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct App {
     /// Absolute path to the directory where the Lingo.toml file is located.
@@ -170,8 +318,15 @@ pub struct App {
     pub target: TargetLanguage,
     /// platform for which this program should be compiled
     pub platform: Platform,
+    /// board the app is built for on embedded platforms
+    pub board: Option<String>,
+    /// compilers and flags the app is cross-compiled with
+    pub toolchain: Option<Toolchain>,
     /// target properties of that lingua-franca app
     pub properties: AppTargetProperties,
+    /// directory with the LF files of every resolved library by package name, the imports of the
+    /// app are checked against them
+    pub include_paths: BTreeMap<String, PathBuf>,
 }
 
 impl AppFile {
@@ -204,7 +359,7 @@ impl AppFile {
             .to_string();
         let main_reactor_name = &temp[..temp.len() - 3];
 
-        App {
+        let mut app = App {
             root_path: path.to_path_buf(),
             name,
             output_root: path.join(OUTPUT_DIRECTORY),
@@ -212,8 +367,15 @@ impl AppFile {
             main_reactor_name: main_reactor_name.to_string(),
             target: self.target,
             platform: self.platform.unwrap_or(Platform::Native),
+            board: self.board,
+            toolchain: None,
             properties: self.properties.from(path),
+            include_paths: BTreeMap::new(),
+        };
+        if let Some(toolchain) = self.toolchain {
+            app.use_toolchain(toolchain.from(path));
         }
+        app
     }
 }
 
@@ -248,9 +410,17 @@ impl LibraryFile {
 
 impl App {
     pub fn build_system(&self, which: &WhichCapability) -> BuildSystem {
+        match self.platform {
+            Platform::Zephyr => return BuildSystem::West,
+            Platform::RP2040 => return BuildSystem::Pico,
+            _ => {}
+        }
         match self.target {
             TargetLanguage::C => CMake,
             TargetLanguage::Cpp => CMake,
+            TargetLanguage::UC => CMake,
+            TargetLanguage::Rust => BuildSystem::Cargo,
+            TargetLanguage::Python => BuildSystem::Pip,
             TargetLanguage::TypeScript => {
                 if which("pnpm").is_ok() {
                     BuildSystem::Pnpm
@@ -261,12 +431,25 @@ impl App {
             _ => LFC,
         }
     }
+    /// Compiles the app with the toolchain. A named toolchain gets a build directory of its own,
+    /// so the app can be built for several targets side by side.
+    pub fn use_toolchain(&mut self, toolchain: Toolchain) {
+        self.output_root = self.root_path.join(OUTPUT_DIRECTORY);
+        if let Some(name) = toolchain.name() {
+            self.output_root.push(name);
+        }
+        self.toolchain = Some(toolchain);
+    }
     pub fn src_gen_dir(&self) -> PathBuf {
         self.output_root.join("src-gen")
     }
     pub fn executable_path(&self) -> PathBuf {
         let mut p = self.output_root.join("bin");
-        if self.target == TargetLanguage::TypeScript {
+        if self.platform == Platform::Zephyr {
+            p.push(self.name.clone() + ".elf")
+        } else if self.platform == Platform::RP2040 {
+            p.push(self.name.clone() + ".uf2")
+        } else if self.target == TargetLanguage::TypeScript {
             p.push(self.name.clone() + ".js")
         } else {
             p.push(&self.name);
@@ -348,6 +531,8 @@ impl ConfigFile {
                 main: Some(spec.path),
                 target: spec.target,
                 platform: Some(init_args.platform),
+                board: None,
+                toolchain: None,
                 properties: Default::default(),
             })
             .collect::<Vec<_>>();
@@ -370,6 +555,10 @@ impl ConfigFile {
             dependencies: HashMap::default(),
             apps: Some(app_specs),
             library: Option::default(),
+            registry: None,
+            patch: HashMap::default(),
+            features: HashMap::default(),
+            policy: None,
         };
         Ok(result)
     }
@@ -416,12 +605,18 @@ impl ConfigFile {
         let tmp_path = dir.path();
 
         let git_rev = if target_language == UC {
-            Some(GitLock::Branch("origin/reactor-uc".to_string()))
+            Some(GitLock::Branch("reactor-uc".to_string()))
         } else {
             None
         };
 
-        clone(GitUrl::from(url), tmp_path, git_rev)?;
+        clone(
+            GitUrl::from(url),
+            tmp_path,
+            git_rev,
+            Default::default(),
+            None,
+        )?;
 
         // Copy the cloned template repo into the project directory
         copy_recursively(tmp_path, Path::new("."))?;
@@ -439,7 +634,9 @@ impl ConfigFile {
     ) -> BuildResult {
         self.setup_template_repo(url, target_language, clone)?;
         remove_file(".gitignore")?;
-        remove_dir_all(Path::new(".git"))?;
+        if Path::new(".git").exists() {
+            remove_dir_all(Path::new(".git"))?;
+        }
         Ok(())
     }
 
@@ -491,10 +688,19 @@ impl ConfigFile {
     }
 
     /// The `path` is the path to the directory containing the Lingo.toml file.
-    pub fn to_config(self, path: &Path) -> Config {
+    pub fn to_config(self, path: &Path) -> anyhow::Result<Config> {
         let package_name = &self.package.name;
 
-        Config {
+        let registry = match self.registry {
+            Some(registry) => Some(
+                registry
+                    .index_url(path)
+                    .map_err(|e| anyhow::anyhow!("invalid registry index: {e}"))?,
+            ),
+            None => None,
+        };
+
+        Ok(Config {
             //properties: self.properties,
             apps: self
                 .apps
@@ -504,7 +710,122 @@ impl ConfigFile {
                 .collect(),
             package: self.package.clone(),
             library: self.library.map(|lib| lib.convert(package_name, path)),
-            dependencies: self.dependencies,
-        }
+            dependencies: self
+                .dependencies
+                .into_iter()
+                .map(|(name, details)| (name, details.relative_to(path)))
+                .collect(),
+            registry,
+            patches: self
+                .patch
+                .into_iter()
+                .map(|(name, details)| (name, details.relative_to(path)))
+                .collect(),
+            features: self
+                .features
+                .into_iter()
+                .map(|(name, feature)| (name, feature.convert(path)))
+                .collect(),
+            policy: self.policy.unwrap_or_default(),
+        })
     }
 }
+
+#[test]
+fn test_toolchain_build_directories() {
+    let package = Path::new("/work/pkg");
+    let file: AppFile = toml::from_str(
+        "target = \"C\"\nproperties = {}\n[toolchain]\nfile = \"cmake/aarch64.cmake\"\ncc = \"aarch64-linux-gnu-gcc\"\nflags = [\"-O2\", \"-march=armv8-a\"]",
+    )
+    .unwrap();
+    let mut app = file.convert("pkg", package);
+    let toolchain = app.toolchain.clone().unwrap();
+    assert_eq!(toolchain.file, Some(package.join("cmake/aarch64.cmake")));
+    assert_eq!(toolchain.name().as_deref(), Some("aarch64"));
+    assert_eq!(app.output_root, package.join("build/aarch64"));
+    assert_eq!(app.executable_path(), package.join("build/aarch64/bin/pkg"));
+
+    // the triple takes precedence over the name of the toolchain file
+    app.use_toolchain(Toolchain {
+        triple: Some("x86_64-linux-gnu".to_string()),
+        ..toolchain.clone()
+    });
+    assert_eq!(app.output_root, package.join("build/x86_64-linux-gnu"));
+
+    app.use_toolchain(Toolchain {
+        cc: Some("clang".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(app.output_root, package.join("build"));
+
+    // a toolchain file of the command line only replaces the file
+    let cli = Toolchain::from_file(PathBuf::from("/work/x86_64.cmake"));
+    let merged = toolchain.clone().merge(cli);
+    assert_eq!(merged.file, Some(PathBuf::from("/work/x86_64.cmake")));
+    assert_eq!(
+        merged,
+        Toolchain {
+            file: merged.file.clone(),
+            ..toolchain
+        }
+    );
+}
+
+#[test]
+fn test_enable_root_features() {
+    let package = tempdir().unwrap();
+    std::fs::create_dir_all(package.path().join("src")).unwrap();
+    write(package.path().join("src/Main.lf"), "target C\n").unwrap();
+    let file: ConfigFile = toml::from_str(
+        r#"
+[package]
+name = "robot"
+version = "0.1.0"
+
+[dependencies]
+tracer = { version = ">=1.0.0", path = "../tracer", optional = true }
+
+[features]
+default = ["tracer"]
+sim = { properties = { sources = ["./c/sim.c"] } }
+
+[[app]]
+name = "robot"
+target = "C"
+main = "src/Main.lf"
+properties = {}
+"#,
+    )
+    .unwrap();
+    let config = file.to_config(package.path()).unwrap();
+    let used = |dependencies: Vec<(String, PackageDetails)>| -> Vec<String> {
+        dependencies.into_iter().map(|(name, _)| name).collect()
+    };
+
+    let default = FeatureRequest::default_features();
+    assert_eq!(
+        used(config.active_dependencies(&default).unwrap()),
+        ["tracer"]
+    );
+
+    let sim = FeatureRequest {
+        features: ["sim".to_string()].into(),
+        default: false,
+    };
+    let mut enabled = config.clone();
+    assert!(used(enabled.enable_features(&sim).unwrap()).is_empty());
+    assert_eq!(
+        enabled.apps[0].properties.sources,
+        [package.path().join("c/sim.c")]
+    );
+
+    let mut enabled = config.clone();
+    enabled.enable_features(&default).unwrap();
+    assert!(enabled.apps[0].properties.sources.is_empty());
+
+    let unknown = FeatureRequest {
+        features: ["turbo".to_string()].into(),
+        default: true,
+    };
+    assert!(config.active_dependencies(&unknown).is_err());
+}
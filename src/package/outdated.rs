@@ -0,0 +1,204 @@
+use colored::Colorize;
+use url::Url;
+use versions::{Requirement, Versioning};
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::package::{
+    lock::{DependencyLock, PackageLockSourceType},
+    management::version_from_tag,
+    registry,
+    tree::PackageDetails,
+};
+use crate::{DownloadCapability, GitListTagsCap, GitUrl};
+
+/// Versions of a locked package
+#[derive(Debug)]
+pub struct OutdatedPackage {
+    pub name: String,
+    pub locked: Versioning,
+    /// newest version that satisfies every requirement on the package
+    pub compatible: Option<Versioning>,
+    /// newest version the source provides
+    pub latest: Option<Versioning>,
+    /// the package is a dependency of the project itself
+    pub direct: bool,
+}
+
+/// Looks up the versions the source of every locked package provides. Git sources provide
+/// their version tags, registry sources the versions published in the index. Path and tarball
+/// sources only provide a single version.
+pub fn check(
+    lock: &DependencyLock,
+    dependencies: &[(String, PackageDetails)],
+    list_tags: &GitListTagsCap,
+    download: &DownloadCapability,
+) -> anyhow::Result<Vec<OutdatedPackage>> {
+    // requirements from the Lingo.toml and from the locked packages
+    let mut requirements: HashMap<&str, Vec<Requirement>> = HashMap::new();
+    for (name, details) in dependencies {
+        requirements
+            .entry(name)
+            .or_default()
+            .push(details.version.clone());
+    }
+    for package in lock.dependencies.values() {
+        for (name, requirement) in &package.dependencies {
+            if let Some(requirement) = Requirement::new(requirement) {
+                requirements.entry(name).or_default().push(requirement);
+            }
+        }
+    }
+
+    let mut outdated = Vec::new();
+    for (name, package) in &lock.dependencies {
+        let uri = &package.source.uri;
+        let mut available: Vec<Versioning> = match package.source.source_type {
+            PackageLockSourceType::GIT => list_tags(GitUrl::from(uri.as_str()), false)?
+                .iter()
+                .filter_map(|tag| version_from_tag(tag))
+                .collect(),
+            PackageLockSourceType::REGISTRY => {
+                registry::read_index(&Url::from_str(uri)?, name, download)?
+                    .into_iter()
+                    .map(|entry| entry.version)
+                    .collect()
+            }
+            PackageLockSourceType::PATH | PackageLockSourceType::TARBALL => vec![],
+        };
+        available.sort();
+
+        let requirements = requirements.get(name.as_str());
+        outdated.push(OutdatedPackage {
+            name: name.clone(),
+            locked: package.version.clone(),
+            compatible: available
+                .iter()
+                .rev()
+                .find(|version| {
+                    requirements
+                        .into_iter()
+                        .flatten()
+                        .all(|requirement| requirement.matches(version))
+                })
+                .cloned(),
+            latest: available.last().cloned(),
+            direct: dependencies
+                .iter()
+                .any(|(dependency, _)| dependency == name),
+        });
+    }
+
+    Ok(outdated)
+}
+
+/// prints one line per package, versions newer than the locked one are highlighted
+pub fn print(packages: &[OutdatedPackage]) {
+    let width = packages
+        .iter()
+        .map(|package| package.name.len() + 1)
+        .max()
+        .unwrap_or_default()
+        .max("Name".len());
+    let column = |version: &Option<Versioning>, locked: &Versioning| match version {
+        Some(version) if version > locked => format!("{:<12}", version.to_string())
+            .yellow()
+            .bold()
+            .to_string(),
+        Some(version) => format!("{:<12}", version.to_string()),
+        None => format!("{:<12}", "-"),
+    };
+
+    println!(
+        "{:<width$}  {:<12}{:<12}{:<12}",
+        "Name", "Locked", "Compatible", "Latest"
+    );
+    for package in packages {
+        let name = if package.direct {
+            package.name.clone()
+        } else {
+            // transitive dependencies are indented
+            format!(" {}", package.name)
+        };
+        println!(
+            "{:<width$}  {:<12}{}{}",
+            name,
+            package.locked.to_string(),
+            column(&package.compatible, &package.locked),
+            column(&package.latest, &package.locked)
+        );
+    }
+}
+
+#[test]
+fn test_outdated_versions() {
+    let lock = toml::from_str::<DependencyLock>(
+        r#"
+version = 2
+
+[[package]]
+name = "alpha"
+version = "1.0.0"
+source = "git+https://example.com/alpha.git#0123abcd"
+checksum = "aa"
+dependencies = { beta = "^0.2.0" }
+
+[[package]]
+name = "beta"
+version = "0.2.0"
+source = "git+https://example.com/beta.git#4567cdef"
+checksum = "bb"
+
+[[package]]
+name = "gamma"
+version = "0.1.0"
+source = "path+../gamma"
+checksum = "cc"
+"#,
+    )
+    .unwrap();
+    let dependencies: Vec<(String, PackageDetails)> = vec![
+        (
+            "alpha".to_string(),
+            toml::from_str("version = \"^1.0.0\"\ngit = \"https://example.com/alpha.git\"")
+                .unwrap(),
+        ),
+        (
+            "gamma".to_string(),
+            toml::from_str("version = \"^0.1.0\"\npath = \"../gamma\"").unwrap(),
+        ),
+    ];
+    let list_tags: GitListTagsCap = Box::new(|url, _| {
+        let url: &str = url.into();
+        let tags: &[&str] = if url.contains("alpha") {
+            &["v1.0.0", "v1.2.0", "v2.0.0", "nightly"]
+        } else {
+            &["v0.2.0", "v0.2.5", "v0.3.0"]
+        };
+        Ok(tags.iter().map(|tag| tag.to_string()).collect())
+    });
+    let download: DownloadCapability =
+        Box::new(|url| Err(crate::DownloadError::NotFound(url.to_string())));
+
+    let outdated = check(&lock, &dependencies, &list_tags, &download).unwrap();
+    let versions: Vec<(&str, Option<String>, Option<String>, bool)> = outdated
+        .iter()
+        .map(|package| {
+            (
+                package.name.as_str(),
+                package.compatible.as_ref().map(Versioning::to_string),
+                package.latest.as_ref().map(Versioning::to_string),
+                package.direct,
+            )
+        })
+        .collect();
+    assert_eq!(
+        versions,
+        [
+            ("alpha", Some("1.2.0".into()), Some("2.0.0".into()), true),
+            ("beta", Some("0.2.5".into()), Some("0.3.0".into()), false),
+            ("gamma", None, None, true),
+        ]
+    );
+}
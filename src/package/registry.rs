@@ -0,0 +1,202 @@
+use serde_derive::{Deserialize, Serialize};
+use url::Url;
+use versions::{Op, Requirement, Versioning};
+
+use std::path::{Path, PathBuf};
+
+use crate::package::{deserialize_version, management::download, serialize_version};
+use crate::util::errors::LingoError;
+use crate::{DownloadCapability, DownloadError};
+
+/// The `[registry]` section inside the Lingo.toml
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RegistryFile {
+    /// url of the index or path to a local index directory
+    pub index: String,
+}
+
+impl RegistryFile {
+    /// Relative paths are interpreted relative to the directory containing the Lingo.toml
+    pub fn index_url(&self, base_path: &Path) -> anyhow::Result<Url> {
+        let url = match Url::parse(&self.index) {
+            Ok(url) => url,
+            Err(_) => {
+                let path = base_path.join(&self.index);
+                let path = std::fs::canonicalize(&path).unwrap_or(path);
+                Url::from_directory_path(&path)
+                    .map_err(|()| anyhow::anyhow!("invalid registry index {}", path.display()))?
+            }
+        };
+        Ok(as_directory(url))
+    }
+}
+
+/// makes sure the url ends with a slash so that joining appends to the path instead of
+/// replacing the last segment
+pub fn as_directory(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url
+}
+
+/// A single published version of a package inside the registry index
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct RegistryEntry {
+    #[serde(
+        serialize_with = "serialize_version",
+        deserialize_with = "deserialize_version"
+    )]
+    pub version: Versioning,
+    /// location of the archive, relative urls are resolved against the index
+    pub tarball: String,
+    /// sha256 of the archive
+    pub sha256: String,
+    /// directory inside the archive that contains the package
+    #[serde(rename = "strip-prefix", default)]
+    pub strip_prefix: Option<PathBuf>,
+}
+
+/// Content of `<index>/<package-name>.toml`
+#[derive(Deserialize, Serialize, Default)]
+pub struct RegistryIndexFile {
+    #[serde(rename = "version", default)]
+    pub versions: Vec<RegistryEntry>,
+}
+
+impl RegistryEntry {
+    pub fn tarball_url(&self, index: &Url) -> anyhow::Result<Url> {
+        Ok(index.join(&self.tarball)?)
+    }
+}
+
+/// Reads all published versions of a package from the index
+pub fn read_index(
+    index: &Url,
+    name: &str,
+    download_cap: &DownloadCapability,
+) -> anyhow::Result<Vec<RegistryEntry>> {
+    let location = as_directory(index.clone()).join(&format!("{name}.toml"))?;
+    let content = match download(&location, download_cap) {
+        Ok(content) => content,
+        Err(e) if is_not_found(&e) => {
+            return Err(
+                LingoError::PackageNotInRegistry(name.to_string(), index.to_string()).into(),
+            )
+        }
+        Err(e) => return Err(e.context(format!("cannot read the registry index {index}"))),
+    };
+    let file = toml::from_str::<RegistryIndexFile>(&String::from_utf8(content)?)
+        .map_err(|e| anyhow::anyhow!("invalid registry entry {location}: {e}"))?;
+    Ok(file.versions)
+}
+
+/// only a missing entry means that the registry doesn't know the package
+fn is_not_found(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<DownloadError>(),
+        Some(DownloadError::NotFound(_))
+    ) || error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// Selects the newest version from the index which satisfies the requirement
+pub fn select<'a>(
+    entries: &'a [RegistryEntry],
+    requirement: &Requirement,
+) -> Option<&'a RegistryEntry> {
+    entries
+        .iter()
+        .filter(|entry| requirement.matches(&entry.version))
+        .max_by(|a, b| a.version.cmp(&b.version))
+}
+
+/// Requirement that only matches exactly the given version
+pub fn exact_requirement(version: &Versioning) -> Requirement {
+    Requirement {
+        op: Op::Exact,
+        version: Some(version.clone()),
+    }
+}
+
+#[test]
+fn test_registry_index() {
+    let index = tempfile::tempdir().unwrap();
+    std::fs::write(
+        index.path().join("mqtt.toml"),
+        r#"
+[[version]]
+version = "0.1.0"
+tarball = "mqtt/mqtt-0.1.0.tar.gz"
+sha256 = "aa"
+
+[[version]]
+version = "0.2.1"
+tarball = "https://example.com/mqtt-0.2.1.tar.gz"
+sha256 = "bb"
+strip-prefix = "mqtt-0.2.1"
+
+[[version]]
+version = "1.0.0"
+tarball = "mqtt/mqtt-1.0.0.tar.gz"
+sha256 = "cc"
+"#,
+    )
+    .unwrap();
+    std::fs::write(index.path().join("broken.toml"), "[[version]]\nversion = 1").unwrap();
+
+    let offline: DownloadCapability = Box::new(|url| panic!("{url} is a local index"));
+    let url = RegistryFile {
+        index: index.path().display().to_string(),
+    }
+    .index_url(Path::new("/"))
+    .unwrap();
+    let entries = read_index(&url, "mqtt", &offline).unwrap();
+    assert_eq!(entries.len(), 3);
+
+    // the newest version that satisfies the requirement is selected
+    let select_version = |requirement: &str| {
+        select(&entries, &Requirement::new(requirement).unwrap())
+            .map(|entry| entry.version.to_string())
+    };
+    assert_eq!(select_version(">=0.1").as_deref(), Some("1.0.0"));
+    assert_eq!(select_version("^0.2.0").as_deref(), Some("0.2.1"));
+    assert_eq!(select_version("=0.1.0").as_deref(), Some("0.1.0"));
+    assert_eq!(select_version(">=2.0"), None);
+
+    // relative tarball urls are resolved against the index
+    assert_eq!(
+        entries[0].tarball_url(&url).unwrap(),
+        url.join("mqtt/mqtt-0.1.0.tar.gz").unwrap()
+    );
+    assert_eq!(
+        entries[1].tarball_url(&url).unwrap().as_str(),
+        "https://example.com/mqtt-0.2.1.tar.gz"
+    );
+
+    let not_in_registry = |error: anyhow::Error| {
+        matches!(
+            error.downcast_ref::<LingoError>(),
+            Some(LingoError::PackageNotInRegistry(..))
+        )
+    };
+    assert!(not_in_registry(
+        read_index(&url, "missing", &offline).unwrap_err()
+    ));
+    assert!(!not_in_registry(
+        read_index(&url, "broken", &offline).unwrap_err()
+    ));
+
+    // only a missing entry on the server means the package isn't in the registry
+    let remote = Url::parse("https://registry.example.com/index/").unwrap();
+    let missing: DownloadCapability = Box::new(|url| Err(DownloadError::NotFound(url.to_string())));
+    assert!(not_in_registry(
+        read_index(&remote, "mqtt", &missing).unwrap_err()
+    ));
+    let unavailable: DownloadCapability =
+        Box::new(|url| Err(DownloadError::Failed(format!("{url} returned 503"))));
+    let error = read_index(&remote, "mqtt", &unavailable).unwrap_err();
+    assert!(!not_in_registry(error));
+}
@@ -0,0 +1,501 @@
+use versions::{Requirement, Versioning};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use crate::package::{
+    features::{self, FeatureRequest},
+    tree::PackageDetails,
+};
+
+/// The package which introduced a requirement
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Origin {
+    /// requirement from the Lingo.toml of the project that is built
+    Root,
+    /// requirement from the Lingo.toml of the given package version
+    Package(String, Versioning),
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Root => write!(f, "the root package"),
+            Origin::Package(name, version) => write!(f, "{name} {version}"),
+        }
+    }
+}
+
+/// A version requirement together with the package that requested it
+#[derive(Clone, Debug)]
+pub struct Constraint {
+    pub requirement: Requirement,
+    pub origin: Origin,
+}
+
+/// The dependencies and features declared by one version of a package
+#[derive(Clone, Debug, Default)]
+pub struct Manifest {
+    pub dependencies: Vec<(String, PackageDetails)>,
+    /// features of the package and what they enable
+    pub features: HashMap<String, Vec<String>>,
+}
+
+/// Gives the resolver access to the available versions of packages and their dependencies.
+pub trait DependencyProvider {
+    /// all versions of the package that can be selected
+    fn available_versions(
+        &mut self,
+        name: &str,
+        source: &PackageDetails,
+    ) -> anyhow::Result<Vec<Versioning>>;
+
+    /// the dependencies and features declared by the given version of the package
+    fn manifest(
+        &mut self,
+        name: &str,
+        source: &PackageDetails,
+        version: &Versioning,
+    ) -> anyhow::Result<Manifest>;
+}
+
+/// A package selected by the resolver
+#[derive(Clone, Debug)]
+pub struct ResolvedPackage {
+    pub name: String,
+    pub version: Versioning,
+    /// source of the package, taken from the first declaration of the package
+    pub source: PackageDetails,
+    /// names of the packages this package depends on
+    pub dependencies: Vec<String>,
+    /// all requirements that were placed on this package
+    pub constraints: Vec<Constraint>,
+    /// enabled features of this package
+    pub features: BTreeSet<String>,
+}
+
+/// Returned when no set of package versions satisfies all requirements.
+#[derive(Debug)]
+pub struct ResolutionError {
+    /// package for which no version could be selected
+    pub package: String,
+    /// requirements on that package which cannot be satisfied together
+    pub constraints: Vec<Constraint>,
+    /// versions of the package that were considered
+    pub available: Vec<Versioning>,
+    /// versions of packages that could not be used, like tags with a broken Lingo.toml
+    pub unavailable: Vec<(String, Versioning, String)>,
+    /// explanation why the packages requesting the conflicting requirements were selected
+    derivation: Vec<String>,
+    /// packages whose selected version contributed to this conflict
+    culprits: BTreeSet<String>,
+}
+
+impl Display for ResolutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "cannot find a version of {} that satisfies all requirements:",
+            self.package
+        )?;
+        for line in &self.derivation {
+            writeln!(f, "  {line}")?;
+        }
+        for (name, version, reason) in &self.unavailable {
+            writeln!(f, "  {name} {version} cannot be used: {reason}")?;
+        }
+        if self.available.is_empty() {
+            write!(f, "  no versions of {} are available", self.package)
+        } else {
+            let available = self
+                .available
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "  available versions of {}: {available}", self.package)
+        }
+    }
+}
+
+impl Error for ResolutionError {}
+
+/// Why the search for a solution stopped
+enum Failure {
+    Conflict(Box<ResolutionError>),
+    Provider(anyhow::Error),
+}
+
+#[derive(Clone, Default)]
+struct State {
+    /// selected version of every package that has been decided
+    decisions: BTreeMap<String, Versioning>,
+    /// requirements placed on every known package
+    constraints: BTreeMap<String, Vec<Constraint>>,
+    /// the first declaration of a package determines its source
+    sources: BTreeMap<String, PackageDetails>,
+    /// dependencies of the decided packages
+    edges: BTreeMap<String, Vec<String>>,
+    /// features every known package has been asked for
+    requests: BTreeMap<String, FeatureRequest>,
+    /// dependencies and features declared by the decided packages
+    manifests: BTreeMap<String, Manifest>,
+    /// request that was used to determine the used dependencies of a decided package,
+    /// together with the features that were enabled by it
+    activated: BTreeMap<String, (FeatureRequest, BTreeSet<String>)>,
+    /// packages in the order they were discovered
+    order: Vec<String>,
+}
+
+impl State {
+    fn add_dependencies(&mut self, origin: &Origin, dependencies: Vec<(String, PackageDetails)>) {
+        for (name, details) in dependencies {
+            if !self.sources.contains_key(&name) {
+                self.order.push(name.clone());
+                self.sources.insert(name.clone(), details.clone());
+            }
+            self.requests.entry(name.clone()).or_default().add(&details);
+            self.constraints.entry(name).or_default().push(Constraint {
+                requirement: details.version,
+                origin: origin.clone(),
+            });
+        }
+    }
+
+    /// enables the requested features of a decided package, which determines the dependencies
+    /// it uses. Decided packages whose requested features grow as a consequence are updated too.
+    fn activate(&mut self, name: &str) -> anyhow::Result<()> {
+        let mut pending = vec![name.to_string()];
+        while let Some(name) = pending.pop() {
+            let request = self.requests.get(&name).cloned().unwrap_or_default();
+            if matches!(self.activated.get(&name), Some((activated, _)) if *activated == request) {
+                continue;
+            }
+
+            let manifest = &self.manifests[&name];
+            let activation =
+                features::activate(&name, &manifest.dependencies, &manifest.features, &request)?;
+            let origin = Origin::Package(name.clone(), self.decisions[&name].clone());
+            let known = self.edges.get(&name).cloned().unwrap_or_default();
+            self.edges.insert(
+                name.clone(),
+                activation
+                    .dependencies
+                    .iter()
+                    .map(|(dependency, _)| dependency.clone())
+                    .collect(),
+            );
+
+            for (dependency, details) in activation.dependencies {
+                let previous = self.requests.get(&dependency).cloned();
+                if known.contains(&dependency) {
+                    self.requests
+                        .entry(dependency.clone())
+                        .or_default()
+                        .add(&details);
+                } else {
+                    self.add_dependencies(&origin, vec![(dependency.clone(), details)]);
+                }
+                if self.decisions.contains_key(&dependency)
+                    && self.requests.get(&dependency) != previous.as_ref()
+                {
+                    pending.push(dependency);
+                }
+            }
+            self.activated.insert(name, (request, activation.features));
+        }
+        Ok(())
+    }
+
+    fn next_undecided(&self) -> Option<String> {
+        self.order
+            .iter()
+            .find(|name| !self.decisions.contains_key(*name))
+            .cloned()
+    }
+
+    fn satisfies_all(&self, name: &str, version: &Versioning) -> bool {
+        self.constraints
+            .get(name)
+            .map(|constraints| {
+                constraints
+                    .iter()
+                    .all(|constraint| constraint.requirement.matches(version))
+            })
+            .unwrap_or(true)
+    }
+
+    /// explains why the requirements on a package can't be satisfied by listing every
+    /// requirement together with the chain of requirements that lead to its origin
+    fn conflict(&self, name: &str, available: Vec<Versioning>) -> ResolutionError {
+        let constraints = self.constraints.get(name).cloned().unwrap_or_default();
+        let mut derivation = Vec::new();
+        let mut culprits = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        self.explain(name, 0, &mut derivation, &mut culprits, &mut visited);
+
+        ResolutionError {
+            package: name.to_string(),
+            constraints,
+            available,
+            unavailable: vec![],
+            derivation,
+            culprits,
+        }
+    }
+
+    fn explain(
+        &self,
+        name: &str,
+        depth: usize,
+        derivation: &mut Vec<String>,
+        culprits: &mut BTreeSet<String>,
+        visited: &mut BTreeSet<String>,
+    ) {
+        if !visited.insert(name.to_string()) {
+            return;
+        }
+        for constraint in self.constraints.get(name).into_iter().flatten() {
+            derivation.push(format!(
+                "{}{} requires {name} {}",
+                "  ".repeat(depth),
+                constraint.origin,
+                constraint.requirement
+            ));
+            if let Origin::Package(parent, _) = &constraint.origin {
+                culprits.insert(parent.clone());
+                self.explain(parent, depth + 1, derivation, culprits, visited);
+            }
+        }
+    }
+}
+
+/// Selects one version for every package reachable from the root dependencies such that all
+/// version requirements are satisfied. Newer versions are preferred. If the search fails the
+/// returned error contains a [`ResolutionError`] describing the conflicting requirements.
+pub fn resolve(
+    root_dependencies: Vec<(String, PackageDetails)>,
+    provider: &mut dyn DependencyProvider,
+) -> anyhow::Result<Vec<ResolvedPackage>> {
+    let mut state = State::default();
+    state.add_dependencies(&Origin::Root, root_dependencies);
+
+    match solve(state, provider) {
+        Ok(state) => Ok(state
+            .order
+            .iter()
+            .map(|name| ResolvedPackage {
+                name: name.clone(),
+                version: state.decisions[name].clone(),
+                source: state.sources[name].clone(),
+                dependencies: state.edges.get(name).cloned().unwrap_or_default(),
+                constraints: state.constraints.get(name).cloned().unwrap_or_default(),
+                features: state
+                    .activated
+                    .get(name)
+                    .map(|(_, features)| features.clone())
+                    .unwrap_or_default(),
+            })
+            .collect()),
+        Err(Failure::Conflict(conflict)) => Err((*conflict).into()),
+        Err(Failure::Provider(error)) => Err(error),
+    }
+}
+
+fn solve(state: State, provider: &mut dyn DependencyProvider) -> Result<State, Failure> {
+    let Some(name) = state.next_undecided() else {
+        return Ok(state);
+    };
+    let source = state.sources[&name].clone();
+
+    let mut available = provider
+        .available_versions(&name, &source)
+        .map_err(Failure::Provider)?;
+    available.sort();
+    available.dedup();
+    available.reverse();
+
+    let candidates: Vec<Versioning> = available
+        .iter()
+        .filter(|version| state.satisfies_all(&name, version))
+        .cloned()
+        .collect();
+
+    let mut last_conflict = None;
+    let mut culprits = BTreeSet::new();
+    let mut unavailable = Vec::new();
+    for version in candidates {
+        // a version that can't be fetched or read is skipped like a version that conflicts
+        let mut next = state.clone();
+        let activated = provider
+            .manifest(&name, &source, &version)
+            .and_then(|manifest| {
+                next.decisions.insert(name.clone(), version.clone());
+                next.manifests.insert(name.clone(), manifest);
+                next.activate(&name)
+            });
+        if let Err(error) = activated {
+            unavailable.push((name.clone(), version, format!("{error:#}")));
+            continue;
+        }
+
+        // the new requirements may rule out packages that have already been decided
+        let violated = next
+            .decisions
+            .iter()
+            .find(|(decided, decided_version)| !next.satisfies_all(decided, decided_version));
+        if let Some((decided, _)) = violated {
+            let decided = decided.clone();
+            let available = next.decisions.get(&decided).cloned().into_iter().collect();
+            let mut conflict = next.conflict(&decided, available);
+            conflict.culprits.insert(decided);
+            culprits.extend(conflict.culprits.iter().cloned());
+            last_conflict = Some(Box::new(conflict));
+            continue;
+        }
+
+        match solve(next, provider) {
+            Ok(solution) => return Ok(solution),
+            // the conflict doesn't depend on the version of this package, trying other
+            // versions of it won't help
+            Err(Failure::Conflict(mut conflict))
+                if !conflict.culprits.contains(&name) && conflict.package != name =>
+            {
+                conflict.unavailable.extend(unavailable);
+                return Err(Failure::Conflict(conflict));
+            }
+            Err(Failure::Conflict(conflict)) => {
+                culprits.extend(conflict.culprits.iter().cloned());
+                last_conflict = Some(conflict);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    // every version of this package failed, so the packages that requested it are at fault
+    let own_conflict = Box::new(state.conflict(&name, available));
+    culprits.extend(own_conflict.culprits.iter().cloned());
+    let mut conflict = last_conflict.unwrap_or(own_conflict);
+    conflict.culprits.extend(culprits);
+    conflict.unavailable.extend(unavailable);
+    Err(Failure::Conflict(conflict))
+}
+
+#[test]
+fn test_resolve_backtracks_and_explains_conflicts() {
+    /// packages are given as `name version` with their dependencies as `name requirement`
+    struct Index(HashMap<(String, String), Vec<(&'static str, &'static str)>>);
+
+    fn details(requirement: &str) -> PackageDetails {
+        toml::from_str(&format!("version = \"{requirement}\"")).unwrap()
+    }
+
+    impl DependencyProvider for Index {
+        fn available_versions(
+            &mut self,
+            name: &str,
+            _: &PackageDetails,
+        ) -> anyhow::Result<Vec<Versioning>> {
+            Ok(self
+                .0
+                .keys()
+                .filter(|(package, _)| package == name)
+                .map(|(_, version)| Versioning::new(version).unwrap())
+                .collect())
+        }
+
+        fn manifest(
+            &mut self,
+            name: &str,
+            _: &PackageDetails,
+            version: &Versioning,
+        ) -> anyhow::Result<Manifest> {
+            Ok(Manifest {
+                dependencies: self.0[&(name.to_string(), version.to_string())]
+                    .iter()
+                    .map(|(dep, requirement)| (dep.to_string(), details(requirement)))
+                    .collect(),
+                features: HashMap::new(),
+            })
+        }
+    }
+
+    let mut index = Index(HashMap::from([
+        (("a".into(), "1.0.0".into()), vec![("b", "^1.0.0")]),
+        (("a".into(), "2.0.0".into()), vec![("b", "^2.0.0")]),
+        (("b".into(), "1.0.0".into()), vec![]),
+        (("b".into(), "1.2.0".into()), vec![]),
+        (("b".into(), "2.0.0".into()), vec![]),
+    ]));
+
+    let resolved = resolve(
+        vec![
+            ("a".into(), details(">=1.0.0")),
+            ("b".into(), details("<2.0.0")),
+        ],
+        &mut index,
+    )
+    .unwrap();
+    let selected: Vec<String> = resolved
+        .iter()
+        .map(|package| format!("{} {}", package.name, package.version))
+        .collect();
+    assert_eq!(selected, ["a 1.0.0", "b 1.2.0"]);
+
+    let error = resolve(
+        vec![
+            ("a".into(), details("^2.0.0")),
+            ("b".into(), details("^1.0.0")),
+        ],
+        &mut index,
+    )
+    .unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("cannot find a version of b"));
+    assert!(message.contains("a 2.0.0 requires b ^2.0.0"));
+    assert!(message.contains("the root package requires a ^2.0.0"));
+}
+
+#[test]
+fn test_resolve_skips_unavailable_versions() {
+    /// the Lingo.toml of a 2.0.0 can't be read
+    struct BrokenRelease;
+
+    impl DependencyProvider for BrokenRelease {
+        fn available_versions(
+            &mut self,
+            _: &str,
+            _: &PackageDetails,
+        ) -> anyhow::Result<Vec<Versioning>> {
+            Ok(vec![
+                Versioning::new("1.0.0").unwrap(),
+                Versioning::new("2.0.0").unwrap(),
+            ])
+        }
+
+        fn manifest(
+            &mut self,
+            _: &str,
+            _: &PackageDetails,
+            version: &Versioning,
+        ) -> anyhow::Result<Manifest> {
+            if version.to_string() == "2.0.0" {
+                return Err(anyhow::anyhow!("missing field `package`"));
+            }
+            Ok(Manifest::default())
+        }
+    }
+
+    let details = |requirement: &str| -> PackageDetails {
+        toml::from_str(&format!("version = \"{requirement}\"")).unwrap()
+    };
+
+    let resolved = resolve(vec![("a".into(), details(">=1.0.0"))], &mut BrokenRelease).unwrap();
+    assert_eq!(resolved[0].version.to_string(), "1.0.0");
+
+    let error = resolve(vec![("a".into(), details("^2.0.0"))], &mut BrokenRelease).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("cannot find a version of a"));
+    assert!(message.contains("a 2.0.0 cannot be used: missing field `package`"));
+}
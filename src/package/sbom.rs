@@ -0,0 +1,608 @@
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::args::{BuildSystem, TargetLanguage};
+use crate::package::{
+    license::{LicenseExpression, PackageLicense},
+    lock::{DependencyLock, PackageLockSourceType},
+    App, PackageDescription,
+};
+use crate::WhichCapability;
+
+/// A locked package as it appears in the bill of materials
+#[derive(Debug)]
+pub struct Component {
+    pub name: String,
+    pub version: String,
+    pub source_type: PackageLockSourceType,
+    pub uri: String,
+    pub rev: Option<String>,
+    /// checksum of the package directory as recorded in Lingo.lock
+    pub checksum: String,
+    /// sha256 of the downloaded archive
+    pub sha256: Option<String>,
+    pub license: Option<String>,
+    /// names of the packages this package depends on
+    pub dependencies: Vec<String>,
+}
+
+/// An app of the project together with the runtime and the tools it is built with
+#[derive(Debug)]
+pub struct AppComponent {
+    pub name: String,
+    pub target: TargetLanguage,
+    pub platform: String,
+    /// reactor runtime of the target language
+    pub runtime: &'static str,
+    /// the runtime is shipped with lfc, so it has the version of lfc
+    pub runtime_version: Option<String>,
+    /// name and version of the code generator and the build tool
+    pub tools: Vec<(String, String)>,
+}
+
+/// collects the locked packages with the licenses from their Lingo.toml
+pub fn components(lock: &DependencyLock, licenses: &[PackageLicense]) -> Vec<Component> {
+    lock.dependencies
+        .values()
+        .map(|package| Component {
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            source_type: package.source.source_type.clone(),
+            uri: package.source.uri.clone(),
+            rev: package.source.rev.clone(),
+            checksum: package.checksum.clone(),
+            sha256: package.sha256.clone(),
+            license: licenses
+                .iter()
+                .find(|license| license.name == package.name)
+                .and_then(|license| license.license.clone()),
+            dependencies: package.dependencies.keys().cloned().collect(),
+        })
+        .collect()
+}
+
+/// name of the reactor runtime that is used for the target language
+fn runtime(target: TargetLanguage) -> &'static str {
+    match target {
+        TargetLanguage::C | TargetLanguage::CCpp => "reactor-c",
+        TargetLanguage::UC => "reactor-uc",
+        TargetLanguage::Cpp => "reactor-cpp",
+        TargetLanguage::Rust => "reactor-rs",
+        TargetLanguage::TypeScript => "reactor-ts",
+        TargetLanguage::Python => "reactor-c-py",
+    }
+}
+
+/// last word of the first line printed by `<program> --version`
+fn tool_version(program: &Path) -> Option<String> {
+    let output = Command::new(program).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()?
+        .split_whitespace()
+        .last()
+        .map(|version| version.trim_start_matches('v').to_string())
+}
+
+/// determines the runtime of the app and the versions of lfc and the build tool
+pub fn describe_app(app: &App, lfc: Option<&Path>, which: &WhichCapability) -> AppComponent {
+    let lfc_version = lfc.and_then(tool_version);
+
+    let mut tools = Vec::new();
+    if let Some(version) = &lfc_version {
+        tools.push(("lfc".to_string(), version.clone()));
+    }
+    let build_tool = match app.build_system(which) {
+        BuildSystem::CMake => Some("cmake"),
+        BuildSystem::Cargo => Some("cargo"),
+        BuildSystem::Npm => Some("npm"),
+        BuildSystem::Pnpm => Some("pnpm"),
+        BuildSystem::Pip => Some("python3"),
+        BuildSystem::West => Some("west"),
+        BuildSystem::Pico => Some("cmake"),
+        BuildSystem::LFC => None,
+    };
+    if let Some(tool) = build_tool {
+        if let Some(version) = which(tool).ok().and_then(|path| tool_version(&path)) {
+            tools.push((tool.to_string(), version));
+        }
+    }
+
+    AppComponent {
+        name: app.name.clone(),
+        target: app.target,
+        platform: format!("{:?}", app.platform),
+        runtime: runtime(app.target),
+        runtime_version: lfc_version,
+        tools,
+    }
+}
+
+/// formats the time as UTC in the form `2024-01-31T12:00:00Z`
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    // converts days since the epoch into a date of the proleptic gregorian calendar
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// location of the package in the form used by both formats, `git+<url>@<rev>` for git
+fn download_location(component: &Component) -> Option<String> {
+    match (&component.source_type, &component.rev) {
+        (PackageLockSourceType::GIT, Some(rev)) => Some(format!("git+{}@{rev}", component.uri)),
+        (PackageLockSourceType::PATH, _) => None,
+        _ => Some(component.uri.clone()),
+    }
+}
+
+/// CycloneDX license entry, a license that isn't a valid SPDX expression is recorded by name
+fn cyclonedx_license(license: &str) -> Value {
+    match LicenseExpression::parse(license) {
+        Ok(_) => json!({ "expression": license }),
+        Err(_) => json!({ "license": { "name": license } }),
+    }
+}
+
+/// SPDX declared license, `NOASSERTION` if there is none or it isn't a valid SPDX expression
+fn spdx_license(license: Option<&String>) -> String {
+    license
+        .filter(|license| LicenseExpression::parse(license).is_ok())
+        .cloned()
+        .unwrap_or_else(|| "NOASSERTION".to_string())
+}
+
+fn bom_ref(name: &str, version: &str) -> String {
+    format!("{name}@{version}")
+}
+
+/// Apps of the same target share their runtime, so it is listed once. Without lfc the version
+/// of the runtime is unknown and the reference only contains its name.
+fn runtime_ref(app: &AppComponent) -> String {
+    match &app.runtime_version {
+        Some(version) => bom_ref(app.runtime, version),
+        None => app.runtime.to_string(),
+    }
+}
+
+/// Creates a CycloneDX 1.5 document. The checksum from Lingo.lock covers the package directory
+/// instead of a single file, so it is recorded as a property and not as a hash.
+pub fn cyclonedx(
+    project: &PackageDescription,
+    direct: &[String],
+    packages: &[Component],
+    apps: &[AppComponent],
+    time: SystemTime,
+) -> Value {
+    let project_ref = bom_ref(&project.name, &project.version.to_string());
+    let versions: BTreeMap<&str, &str> = packages
+        .iter()
+        .map(|package| (package.name.as_str(), package.version.as_str()))
+        .collect();
+    let reference = |name: &String| {
+        versions
+            .get(name.as_str())
+            .map(|version| bom_ref(name, version))
+    };
+
+    let mut components = Vec::new();
+    let mut dependencies = vec![json!({
+        "ref": project_ref,
+        "dependsOn": direct.iter().filter_map(reference).collect::<Vec<_>>(),
+    })];
+
+    for package in packages {
+        let mut component = json!({
+            "type": "library",
+            "bom-ref": bom_ref(&package.name, &package.version),
+            "name": package.name,
+            "version": package.version,
+            "properties": [],
+        });
+        // path dependencies are used as they are and have no checksum
+        if !package.checksum.is_empty() {
+            component["properties"] =
+                json!([{ "name": "lingo:checksum", "value": package.checksum }]);
+        }
+        if let Some(license) = &package.license {
+            component["licenses"] = json!([cyclonedx_license(license)]);
+        }
+        if let Some(sha256) = &package.sha256 {
+            component["hashes"] = json!([{ "alg": "SHA-256", "content": sha256 }]);
+        }
+        let reference_type = match package.source_type {
+            PackageLockSourceType::GIT => "vcs",
+            _ => "distribution",
+        };
+        if let Some(location) = download_location(package) {
+            component["externalReferences"] = json!([{ "type": reference_type, "url": location }]);
+        }
+        if let Some(rev) = &package.rev {
+            component["properties"]
+                .as_array_mut()
+                .expect("properties are an array")
+                .push(json!({ "name": "lingo:git-rev", "value": rev }));
+        }
+        components.push(component);
+        dependencies.push(json!({
+            "ref": bom_ref(&package.name, &package.version),
+            "dependsOn": package.dependencies.iter().filter_map(reference).collect::<Vec<_>>(),
+        }));
+    }
+
+    let mut runtimes = BTreeMap::new();
+    for app in apps {
+        let app_ref = format!("app:{}", app.name);
+        let runtime_ref = runtime_ref(app);
+        runtimes.entry(runtime_ref.clone()).or_insert_with(|| {
+            let mut runtime = json!({
+                "type": "framework",
+                "bom-ref": runtime_ref,
+                "name": app.runtime,
+            });
+            if let Some(version) = &app.runtime_version {
+                runtime["version"] = json!(version);
+            }
+            runtime
+        });
+        let mut properties = vec![
+            json!({ "name": "lingo:target", "value": format!("{:?}", app.target) }),
+            json!({ "name": "lingo:platform", "value": app.platform }),
+        ];
+        for (tool, version) in &app.tools {
+            properties.push(json!({ "name": format!("lingo:toolchain:{tool}"), "value": version }));
+        }
+
+        components.push(json!({
+            "type": "application",
+            "bom-ref": app_ref,
+            "name": app.name,
+            "properties": properties,
+        }));
+        let depends_on: Vec<String> = std::iter::once(runtime_ref)
+            .chain(direct.iter().filter_map(reference))
+            .collect();
+        dependencies.push(json!({ "ref": app_ref, "dependsOn": depends_on }));
+    }
+    components.extend(runtimes.into_values());
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "timestamp": timestamp(time),
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "lingo",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+            },
+            "component": {
+                "type": "application",
+                "bom-ref": project_ref,
+                "name": project.name,
+                "version": project.version.to_string(),
+            },
+        },
+        "components": components,
+        "dependencies": dependencies,
+    })
+}
+
+/// SPDX identifiers may only contain letters, numbers, `.` and `-`
+fn spdx_id(kind: &str, name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("SPDXRef-{kind}-{name}")
+}
+
+/// Creates an SPDX 2.3 document. The checksum from Lingo.lock covers the package directory
+/// instead of a single file, so it is recorded in the package comment.
+pub fn spdx(
+    project: &PackageDescription,
+    direct: &[String],
+    packages: &[Component],
+    apps: &[AppComponent],
+    time: SystemTime,
+) -> Value {
+    let created = timestamp(time);
+    let project_id = spdx_id("Package", &project.name);
+    let namespace = format!(
+        "https://spdx.org/spdxdocs/{}-{}-{:x}",
+        project.name,
+        project.version,
+        Sha1::digest(format!("{}{}{created}", project.name, project.version))
+    );
+    let no_assertion = || "NOASSERTION".to_string();
+
+    let mut spdx_packages = vec![json!({
+        "SPDXID": project_id,
+        "name": project.name,
+        "versionInfo": project.version.to_string(),
+        "downloadLocation": "NOASSERTION",
+        "licenseConcluded": "NOASSERTION",
+        "licenseDeclared": spdx_license(project.license.as_ref()),
+        "copyrightText": "NOASSERTION",
+        "filesAnalyzed": false,
+        "primaryPackagePurpose": "APPLICATION",
+    })];
+    let mut relationships = vec![json!({
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": project_id,
+    })];
+    let depends_on = |from: &str, to: &str| {
+        json!({
+            "spdxElementId": from,
+            "relationshipType": "DEPENDS_ON",
+            "relatedSpdxElement": to,
+        })
+    };
+
+    for package in packages {
+        let id = spdx_id("Package", &package.name);
+        let mut entry = json!({
+            "SPDXID": id,
+            "name": package.name,
+            "versionInfo": package.version,
+            "downloadLocation": download_location(package).unwrap_or_else(no_assertion),
+            "licenseConcluded": "NOASSERTION",
+            "licenseDeclared": spdx_license(package.license.as_ref()),
+            "copyrightText": "NOASSERTION",
+            "filesAnalyzed": false,
+            "primaryPackagePurpose": "LIBRARY",
+        });
+        if !package.checksum.is_empty() {
+            entry["comment"] = json!(format!("Lingo.lock checksum {}", package.checksum));
+        }
+        if let Some(sha256) = &package.sha256 {
+            entry["checksums"] = json!([{ "algorithm": "SHA256", "checksumValue": sha256 }]);
+        }
+        spdx_packages.push(entry);
+        for dependency in &package.dependencies {
+            relationships.push(depends_on(&id, &spdx_id("Package", dependency)));
+        }
+    }
+    for dependency in direct {
+        if packages.iter().any(|package| &package.name == dependency) {
+            relationships.push(depends_on(&project_id, &spdx_id("Package", dependency)));
+        }
+    }
+
+    let mut runtimes = BTreeSet::new();
+    for app in apps {
+        let app_id = spdx_id("App", &app.name);
+        let runtime_id = spdx_id("Runtime", &runtime_ref(app));
+        spdx_packages.push(json!({
+            "SPDXID": app_id,
+            "name": app.name,
+            "downloadLocation": "NOASSERTION",
+            "licenseConcluded": "NOASSERTION",
+            "licenseDeclared": "NOASSERTION",
+            "copyrightText": "NOASSERTION",
+            "filesAnalyzed": false,
+            "primaryPackagePurpose": "APPLICATION",
+            "comment": format!("target {:?}, platform {}", app.target, app.platform),
+        }));
+        let mut runtime = json!({
+            "SPDXID": runtime_id,
+            "name": app.runtime,
+            "downloadLocation": "NOASSERTION",
+            "licenseConcluded": "NOASSERTION",
+            "licenseDeclared": "NOASSERTION",
+            "copyrightText": "NOASSERTION",
+            "filesAnalyzed": false,
+            "primaryPackagePurpose": "FRAMEWORK",
+        });
+        if let Some(version) = &app.runtime_version {
+            runtime["versionInfo"] = json!(version);
+        }
+        if runtimes.insert(runtime_id.clone()) {
+            spdx_packages.push(runtime);
+        }
+        relationships.push(json!({
+            "spdxElementId": project_id,
+            "relationshipType": "CONTAINS",
+            "relatedSpdxElement": app_id,
+        }));
+        relationships.push(depends_on(&app_id, &runtime_id));
+
+        for (tool, version) in &app.tools {
+            let tool_id = spdx_id("Tool", &format!("{}-{tool}", app.name));
+            spdx_packages.push(json!({
+                "SPDXID": tool_id,
+                "name": tool,
+                "versionInfo": version,
+                "downloadLocation": "NOASSERTION",
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": "NOASSERTION",
+                "copyrightText": "NOASSERTION",
+                "filesAnalyzed": false,
+                "primaryPackagePurpose": "APPLICATION",
+            }));
+            relationships.push(json!({
+                "spdxElementId": tool_id,
+                "relationshipType": "BUILD_TOOL_OF",
+                "relatedSpdxElement": app_id,
+            }));
+        }
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": format!("{}-{}", project.name, project.version),
+        "documentNamespace": namespace,
+        "creationInfo": {
+            "created": created,
+            "creators": [format!("Tool: lingo-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": spdx_packages,
+        "relationships": relationships,
+    })
+}
+
+#[test]
+fn test_sbom_documents() {
+    use std::time::Duration;
+
+    let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+    assert_eq!(timestamp(time), "2024-02-29T12:34:56Z");
+
+    let project: PackageDescription =
+        toml::from_str("name = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"").unwrap();
+    let packages = vec![Component {
+        name: "mqtt".to_string(),
+        version: "1.2.0".to_string(),
+        source_type: PackageLockSourceType::GIT,
+        uri: "https://github.com/LF-Community/mqtt.git".to_string(),
+        rev: Some("0123abcd".to_string()),
+        checksum: "aa".to_string(),
+        sha256: None,
+        license: Some("MIT OR Apache-2.0".to_string()),
+        dependencies: vec![],
+    }];
+    let apps = vec![AppComponent {
+        name: "app".to_string(),
+        target: TargetLanguage::C,
+        platform: "Native".to_string(),
+        runtime: "reactor-c",
+        runtime_version: Some("0.8.0".to_string()),
+        tools: vec![("lfc".to_string(), "0.8.0".to_string())],
+    }];
+    let direct = ["mqtt".to_string()];
+
+    let bom = cyclonedx(&project, &direct, &packages, &apps, time);
+    assert_eq!(bom["components"][0]["bom-ref"], "mqtt@1.2.0");
+    assert_eq!(
+        bom["components"][0]["externalReferences"][0]["url"],
+        "git+https://github.com/LF-Community/mqtt.git@0123abcd"
+    );
+    assert_eq!(bom["components"][2]["bom-ref"], "reactor-c@0.8.0");
+    assert_eq!(bom["dependencies"][0]["dependsOn"][0], "mqtt@1.2.0");
+
+    let document = spdx(&project, &direct, &packages, &apps, time);
+    assert_eq!(document["packages"][1]["SPDXID"], "SPDXRef-Package-mqtt");
+    assert_eq!(
+        document["packages"][1]["licenseDeclared"],
+        "MIT OR Apache-2.0"
+    );
+    assert!(document["relationships"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|relationship| relationship["relationshipType"] == "BUILD_TOOL_OF"));
+}
+
+#[test]
+fn test_sbom_shares_runtimes() {
+    let project: PackageDescription =
+        toml::from_str("name = \"robot\"\nversion = \"0.1.0\"").unwrap();
+    let app = |name: &str| AppComponent {
+        name: name.to_string(),
+        target: TargetLanguage::C,
+        platform: "Native".to_string(),
+        runtime: "reactor-c",
+        runtime_version: None,
+        tools: vec![],
+    };
+    let apps = vec![app("controller"), app("simulator")];
+
+    let bom = cyclonedx(&project, &[], &[], &apps, UNIX_EPOCH);
+    let components = bom["components"].as_array().unwrap();
+    let refs: Vec<&str> = components
+        .iter()
+        .map(|component| component["bom-ref"].as_str().unwrap())
+        .collect();
+    assert_eq!(refs, ["app:controller", "app:simulator", "reactor-c"]);
+    assert!(components[2].get("version").is_none());
+    for dependency in &bom["dependencies"].as_array().unwrap()[1..] {
+        assert_eq!(dependency["dependsOn"][0], "reactor-c");
+    }
+
+    let document = spdx(&project, &[], &[], &apps, UNIX_EPOCH);
+    let ids: Vec<&str> = document["packages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|package| package["SPDXID"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            "SPDXRef-Package-robot",
+            "SPDXRef-App-controller",
+            "SPDXRef-Runtime-reactor-c",
+            "SPDXRef-App-simulator",
+        ]
+    );
+}
+
+#[test]
+fn test_sbom_invalid_license() {
+    let project: PackageDescription =
+        toml::from_str("name = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT OR\"").unwrap();
+    let packages = vec![Component {
+        name: "mqtt".to_string(),
+        version: "1.2.0".to_string(),
+        source_type: PackageLockSourceType::TARBALL,
+        uri: "https://example.com/mqtt-1.2.0.tar.gz".to_string(),
+        rev: None,
+        checksum: "aa".to_string(),
+        sha256: None,
+        license: Some("MIT or the Apache license".to_string()),
+        dependencies: vec![],
+    }];
+
+    let bom = cyclonedx(&project, &[], &packages, &[], UNIX_EPOCH);
+    assert_eq!(
+        bom["components"][0]["licenses"],
+        json!([{ "license": { "name": "MIT or the Apache license" } }])
+    );
+
+    let document = spdx(&project, &[], &packages, &[], UNIX_EPOCH);
+    assert_eq!(document["packages"][0]["licenseDeclared"], "NOASSERTION");
+    assert_eq!(document["packages"][1]["licenseDeclared"], "NOASSERTION");
+}
@@ -1,8 +1,12 @@
-use serde::{Deserialize, Serialize, Serializer};
+use serde::de::Error as DeserializationError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+
+use crate::package::management::copy_dir_all;
 
 pub trait CMakeLoader {
     fn read_file(&mut self, path: &str) -> anyhow::Result<AutoCmakeLoad>;
@@ -56,8 +60,61 @@ pub struct LibraryTargetPropertiesFile {
     sources: Vec<PathBuf>,
 
     /// list of files that should be made available to the user
-    #[serde(rename = "sources", default)]
+    #[serde(
+        rename = "artifacts",
+        default,
+        deserialize_with = "deserialize_artifacts"
+    )]
     artifacts: Vec<PathBuf>,
+
+    /// npm packages with their version range, only available for TypeScript
+    #[serde(rename = "npm-dependencies", default)]
+    npm_dependencies: BTreeMap<String, String>,
+
+    /// folders with TypeScript sources that are copied into the generated project
+    #[serde(rename = "ts-sources", default)]
+    ts_sources: Vec<PathBuf>,
+
+    /// pip requirements like `paho-mqtt>=2.0`, only available for Python
+    #[serde(rename = "pip-requirements", default)]
+    pip_requirements: Vec<String>,
+
+    /// folders whose Python modules are made importable by the generated program
+    #[serde(rename = "python-path", default)]
+    python_path: Vec<PathBuf>,
+}
+
+/// Artifacts keep their path inside the output directory of the app, so only relative paths
+/// that stay inside the library are accepted.
+fn deserialize_artifacts<'de, D>(deserializer: D) -> Result<Vec<PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let artifacts = Vec::<PathBuf>::deserialize(deserializer)?;
+    for artifact in &artifacts {
+        let inside = artifact
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        let named = artifact
+            .components()
+            .any(|component| matches!(component, Component::Normal(_)));
+        if !inside || !named {
+            return Err(D::Error::custom(format!(
+                "artifact {} has to be a relative path inside the library",
+                artifact.display()
+            )));
+        }
+    }
+    Ok(artifacts)
+}
+
+/// File or directory of a library that is copied into the output directory of every app
+#[derive(Clone, Debug, PartialEq)]
+pub struct Artifact {
+    /// location inside the library
+    pub source: PathBuf,
+    /// location relative to the output directory of the app
+    pub destination: PathBuf,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -69,7 +126,19 @@ pub struct LibraryTargetProperties {
     pub sources: Vec<PathBuf>,
 
     /// list of files that should be made available to the user
-    pub artifacts: Vec<PathBuf>,
+    pub artifacts: Vec<Artifact>,
+
+    /// npm packages with their version range, only available for TypeScript
+    pub npm_dependencies: BTreeMap<String, String>,
+
+    /// folders with TypeScript sources that are copied into the generated project
+    pub ts_sources: Vec<PathBuf>,
+
+    /// pip requirements, only available for Python
+    pub pip_requirements: Vec<String>,
+
+    /// folders with Python modules that are made importable by the generated program
+    pub python_path: Vec<PathBuf>,
 }
 
 impl LibraryTargetPropertiesFile {
@@ -84,8 +153,31 @@ impl LibraryTargetPropertiesFile {
                     })
                     .unwrap_or_default(),
             ),
-            sources: self.sources,
-            artifacts: self.artifacts,
+            sources: self
+                .sources
+                .into_iter()
+                .map(|source| base_path.join(source))
+                .collect(),
+            artifacts: self
+                .artifacts
+                .into_iter()
+                .map(|artifact| Artifact {
+                    source: base_path.join(&artifact),
+                    destination: artifact,
+                })
+                .collect(),
+            npm_dependencies: self.npm_dependencies,
+            ts_sources: self
+                .ts_sources
+                .into_iter()
+                .map(|folder| base_path.join(folder))
+                .collect(),
+            pip_requirements: self.pip_requirements,
+            python_path: self
+                .python_path
+                .into_iter()
+                .map(|folder| base_path.join(folder))
+                .collect(),
         }
     }
 }
@@ -99,6 +191,14 @@ pub struct AppTargetPropertiesFile {
     /// if the runtime should wait for physical time to catch up
     #[serde(default)]
     pub fast: bool,
+
+    /// interpreter the virtual environment of Python apps is created with
+    #[serde(default)]
+    python: Option<PathBuf>,
+
+    /// pip requirements of Python apps like `numpy>=1.26`
+    #[serde(rename = "pip-requirements", default)]
+    pip_requirements: Vec<String>,
 }
 
 #[derive(Clone, Default)]
@@ -108,6 +208,27 @@ pub struct AppTargetProperties {
 
     /// if the runtime should wait for physical time to catch up
     pub fast: bool,
+
+    /// files of the libraries that are compiled and linked into the app
+    pub sources: Vec<PathBuf>,
+
+    /// files of the libraries that are copied into the output directory
+    pub artifacts: Vec<Artifact>,
+
+    /// npm packages of the libraries
+    pub npm_dependencies: BTreeMap<String, String>,
+
+    /// TypeScript source folders of the libraries
+    pub ts_sources: Vec<PathBuf>,
+
+    /// interpreter the virtual environment of Python apps is created with
+    pub python: Option<PathBuf>,
+
+    /// pip requirements of the app and of the libraries
+    pub pip_requirements: Vec<String>,
+
+    /// Python module folders of the libraries
+    pub python_path: Vec<PathBuf>,
 }
 
 impl AppTargetPropertiesFile {
@@ -123,6 +244,16 @@ impl AppTargetPropertiesFile {
                     .unwrap_or_default(),
             ),
             fast: self.fast,
+            // a bare name like `python3.12` is looked up on the PATH, paths are package relative
+            python: self.python.map(|python| {
+                if python.components().count() > 1 {
+                    base_path.join(python)
+                } else {
+                    python
+                }
+            }),
+            pip_requirements: self.pip_requirements,
+            ..Default::default()
         }
     }
 }
@@ -131,6 +262,71 @@ pub trait MergeTargetProperty {
     fn merge(&mut self, other: &Self) -> anyhow::Result<()>;
 }
 
+/// appends the entries of the parent that aren't contained yet
+fn extend_unique<T: Clone + PartialEq>(own: &mut Vec<T>, parent: &[T]) {
+    for entry in parent {
+        if !own.contains(entry) {
+            own.push(entry.clone());
+        }
+    }
+}
+
+impl MergeTargetProperty for Vec<PathBuf> {
+    fn merge(&mut self, parent: &Vec<PathBuf>) -> anyhow::Result<()> {
+        extend_unique(self, parent);
+        Ok(())
+    }
+}
+
+impl MergeTargetProperty for Vec<String> {
+    fn merge(&mut self, parent: &Vec<String>) -> anyhow::Result<()> {
+        extend_unique(self, parent);
+        Ok(())
+    }
+}
+
+/// adds the npm packages of the parent, the same package may not be required in different versions
+fn merge_npm_dependencies(
+    own: &mut BTreeMap<String, String>,
+    parent: &BTreeMap<String, String>,
+) -> anyhow::Result<()> {
+    for (package, version) in parent {
+        match own.get(package) {
+            Some(other) if other != version => anyhow::bail!(
+                "the npm package {package} is required with the versions {other} and {version}"
+            ),
+            Some(_) => {}
+            None => {
+                own.insert(package.clone(), version.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+impl MergeTargetProperty for Vec<Artifact> {
+    fn merge(&mut self, parent: &Vec<Artifact>) -> anyhow::Result<()> {
+        for artifact in parent {
+            if let Some(other) = self
+                .iter()
+                .find(|other| other.destination == artifact.destination)
+            {
+                if other.source != artifact.source {
+                    anyhow::bail!(
+                        "the artifacts {} and {} are both copied to {}",
+                        other.source.display(),
+                        artifact.source.display(),
+                        artifact.destination.display()
+                    );
+                }
+                continue;
+            }
+            self.push(artifact.clone());
+        }
+        Ok(())
+    }
+}
+
 pub trait MergeTargetProperties {
     fn merge(&mut self, other: &LibraryTargetProperties) -> anyhow::Result<()>;
 }
@@ -138,6 +334,12 @@ pub trait MergeTargetProperties {
 impl MergeTargetProperties for LibraryTargetProperties {
     fn merge(&mut self, partent: &LibraryTargetProperties) -> anyhow::Result<()> {
         self.cmake_include.merge(&partent.cmake_include)?;
+        self.sources.merge(&partent.sources)?;
+        self.artifacts.merge(&partent.artifacts)?;
+        merge_npm_dependencies(&mut self.npm_dependencies, &partent.npm_dependencies)?;
+        self.ts_sources.merge(&partent.ts_sources)?;
+        self.pip_requirements.merge(&partent.pip_requirements)?;
+        self.python_path.merge(&partent.python_path)?;
         Ok(())
     }
 }
@@ -145,6 +347,12 @@ impl MergeTargetProperties for LibraryTargetProperties {
 impl MergeTargetProperties for AppTargetProperties {
     fn merge(&mut self, parent: &LibraryTargetProperties) -> anyhow::Result<()> {
         self.cmake_include.merge(&parent.cmake_include)?;
+        self.sources.merge(&parent.sources)?;
+        self.artifacts.merge(&parent.artifacts)?;
+        merge_npm_dependencies(&mut self.npm_dependencies, &parent.npm_dependencies)?;
+        self.ts_sources.merge(&parent.ts_sources)?;
+        self.pip_requirements.merge(&parent.pip_requirements)?;
+        self.python_path.merge(&parent.python_path)?;
         Ok(())
     }
 }
@@ -159,13 +367,100 @@ impl MergeTargetProperties for GenericTargetProperties {
 }
 
 impl AppTargetProperties {
+    /// writes the cmake includes of the libraries and adds their sources to the main target
     pub fn write_artifacts(&self, library_folder: &Path) -> anyhow::Result<()> {
         let file = library_folder.join("aggregated_cmake_include.cmake");
 
         let mut fd = std::fs::File::create(file)?;
         fd.write_all(self.cmake_include.0.as_ref())?;
+        if !self.sources.is_empty() {
+            writeln!(fd, "\ntarget_sources(${{LF_MAIN_TARGET}} PRIVATE")?;
+            for source in &self.sources {
+                let source = std::fs::canonicalize(source).map_err(|e| {
+                    anyhow::anyhow!("cannot find library source {}: {e}", source.display())
+                })?;
+                // cmake expects forward slashes on every platform
+                writeln!(
+                    fd,
+                    "    \"{}\"",
+                    source.display().to_string().replace('\\', "/")
+                )?;
+            }
+            writeln!(fd, ")")?;
+        }
         fd.flush()?;
 
         Ok(())
     }
+
+    /// copies the artifacts of the libraries into the output directory of the app
+    pub fn copy_artifacts(&self, output_root: &Path) -> anyhow::Result<()> {
+        for artifact in &self.artifacts {
+            let destination = output_root.join(&artifact.destination);
+            if artifact.source.is_dir() {
+                copy_dir_all(&artifact.source, &destination)?;
+            } else {
+                if let Some(parent) = destination.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(&artifact.source, &destination).map_err(|e| {
+                    anyhow::anyhow!("cannot copy artifact {}: {e}", artifact.source.display())
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_library_sources_and_artifacts() {
+    let library = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(library.path().join("c")).unwrap();
+    std::fs::write(library.path().join("c/mqtt.c"), "").unwrap();
+
+    // artifacts can't be copied outside of the output directory
+    for escaping in ["/etc/mqtt.h", "../mqtt.h", "include/../../mqtt.h", "."] {
+        let error =
+            toml::from_str::<LibraryTargetPropertiesFile>(&format!("artifacts = [{escaping:?}]"))
+                .err()
+                .unwrap();
+        assert!(error
+            .to_string()
+            .contains("relative path inside the library"));
+    }
+
+    let file: LibraryTargetPropertiesFile =
+        toml::from_str("sources = [\"c/mqtt.c\"]\nartifacts = [\"include/mqtt.h\"]").unwrap();
+    let properties = file.from(library.path());
+    assert_eq!(
+        properties.artifacts,
+        [Artifact {
+            source: library.path().join("include/mqtt.h"),
+            destination: PathBuf::from("include/mqtt.h"),
+        }]
+    );
+
+    let mut app = AppTargetProperties::default();
+    app.merge(&properties).unwrap();
+    app.merge(&properties).unwrap();
+    assert_eq!(app.sources.len(), 1);
+
+    let mut conflicting = properties.clone();
+    conflicting.artifacts[0].source = PathBuf::from("other/mqtt.h");
+    assert!(app.merge(&conflicting).is_err());
+
+    let npm = |version: &str| LibraryTargetProperties {
+        npm_dependencies: BTreeMap::from([("mqtt".to_string(), version.to_string())]),
+        ..Default::default()
+    };
+    app.merge(&npm("^5.0.0")).unwrap();
+    app.merge(&npm("^5.0.0")).unwrap();
+    assert!(app.merge(&npm("^4.0.0")).is_err());
+
+    let build = tempfile::tempdir().unwrap();
+    app.write_artifacts(build.path()).unwrap();
+    let cmake =
+        std::fs::read_to_string(build.path().join("aggregated_cmake_include.cmake")).unwrap();
+    assert!(cmake.contains("target_sources(${LF_MAIN_TARGET} PRIVATE"));
+    assert!(cmake.contains("mqtt.c\""));
 }
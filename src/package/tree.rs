@@ -1,24 +1,109 @@
-use serde::{Deserialize, Serialize};
+use serde::de::{Error, IgnoredAny, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use url::Url;
 use versions::{Requirement, Versioning};
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::path::PathBuf;
 
 use crate::package::target_properties::LibraryTargetProperties;
 
-#[derive(Clone, Deserialize, Serialize, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ProjectSource {
-    #[serde(rename = "git")]
     Git(Url),
-    #[serde(rename = "tarball")]
     TarBall(Url),
-    #[serde(rename = "path")]
     Path(PathBuf),
-    //#[serde(rename = "empty")]
-    //Empty,
+    /// package index to look the package up in, `None` refers to the default registry
+    Registry(Option<Url>),
+}
+
+/// Parses the url of a git repository. The scp-like syntax `git@host:path` for ssh remotes is
+/// turned into an equivalent `ssh://` url.
+pub fn parse_git_url(url: &str) -> Result<Url, url::ParseError> {
+    match Url::parse(url) {
+        Err(url::ParseError::RelativeUrlWithoutBase) => match url.split_once(':') {
+            Some((host, path)) if !host.contains('/') => {
+                Url::parse(&format!("ssh://{host}/{}", path.trim_start_matches('/')))
+            }
+            _ => Url::parse(url),
+        },
+        result => result,
+    }
+}
+
+/// keys inside a dependency declaration that select the source of a package
+const SOURCE_KEYS: [&str; 4] = ["git", "tarball", "path", "registry"];
+
+impl Serialize for ProjectSource {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            ProjectSource::Git(url) => map.serialize_entry("git", url)?,
+            ProjectSource::TarBall(url) => map.serialize_entry("tarball", url)?,
+            ProjectSource::Path(path) => map.serialize_entry("path", path)?,
+            ProjectSource::Registry(Some(url)) => map.serialize_entry("registry", url)?,
+            ProjectSource::Registry(None) => {}
+        }
+        map.end()
+    }
+}
+
+struct ProjectSourceVisitor;
+
+impl<'de> Visitor<'de> for ProjectSourceVisitor {
+    type Value = ProjectSource;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "at most one of {}", SOURCE_KEYS.join(", "))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut source = None;
+        while let Some(key) = map.next_key::<String>()? {
+            let value = match key.as_str() {
+                "git" => ProjectSource::Git(
+                    parse_git_url(&map.next_value::<String>()?).map_err(A::Error::custom)?,
+                ),
+                "tarball" => ProjectSource::TarBall(map.next_value()?),
+                "path" => ProjectSource::Path(map.next_value()?),
+                "registry" => ProjectSource::Registry(Some(map.next_value()?)),
+                _ => {
+                    // keys of the other fields of the dependency
+                    map.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+            };
+            if source.replace(value).is_some() {
+                return Err(A::Error::custom(format!(
+                    "only one of {} can be specified",
+                    SOURCE_KEYS.join(", ")
+                )));
+            }
+        }
+
+        // dependencies that only specify a version are looked up in the registry
+        Ok(source.unwrap_or(ProjectSource::Registry(None)))
+    }
+}
+
+impl<'de> Deserialize<'de> for ProjectSource {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ProjectSourceVisitor)
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum GitLock {
     #[serde(rename = "tag")]
     Tag(String),
@@ -30,12 +115,31 @@ pub enum GitLock {
     Rev(String),
 }
 
+/// Controls how much of a git repository is fetched
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct GitFetchOptions {
+    /// only fetch the latest commit of the requested branch or tag
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shallow: bool,
+    /// only fetch the requested branch or tag instead of all of them
+    #[serde(
+        rename = "single-branch",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub single_branch: bool,
+    /// only check out what the cached mirror already contains, set by `--offline`
+    #[serde(skip)]
+    pub offline: bool,
+}
+
 /// Dependency with source and version
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct PackageDetails {
     #[serde(
         deserialize_with = "Requirement::deserialize",
-        serialize_with = "Requirement::serialize"
+        serialize_with = "Requirement::serialize",
+        default
     )]
     pub(crate) version: Requirement,
     #[serde(flatten)]
@@ -44,6 +148,45 @@ pub struct PackageDetails {
     pub(crate) git_tag: Option<GitLock>,
     #[serde(skip)]
     pub(crate) git_rev: Option<String>,
+    #[serde(flatten)]
+    pub(crate) git_fetch: GitFetchOptions,
+    /// directory inside the git repository that contains the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) subdir: Option<PathBuf>,
+    /// the source was replaced by an entry of the `[patch]` table
+    #[serde(skip)]
+    pub(crate) patched: bool,
+    /// expected sha256 of a downloaded archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sha256: Option<String>,
+    /// directory inside a downloaded archive that contains the package
+    #[serde(
+        rename = "strip-prefix",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) strip_prefix: Option<PathBuf>,
+    /// features of the package that should be enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) features: Vec<String>,
+    /// the dependency is only used if a feature enables it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) optional: bool,
+    /// enables the `default` feature of the package
+    #[serde(
+        rename = "default-features",
+        default = "enabled",
+        skip_serializing_if = "is_enabled"
+    )]
+    pub(crate) default_features: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn is_enabled(value: &bool) -> bool {
+    *value
 }
 
 #[derive(Clone, Debug)]
@@ -62,6 +205,12 @@ pub struct DependencyTreeNode {
     pub(crate) hash: String,
     /// required dependencies to build this package
     pub(crate) dependencies: Vec<DependencyTreeNode>,
+    /// version requirements this package places on its direct dependencies
+    pub(crate) requirements: BTreeMap<String, Requirement>,
+    /// enabled features of this package
+    pub(crate) features: BTreeSet<String>,
+    /// SPDX license expression from the Lingo.toml of the package
+    pub(crate) license: Option<String>,
     /// required dependencies to build this package
     pub(crate) properties: LibraryTargetProperties,
 }
@@ -76,6 +225,9 @@ impl DependencyTreeNode {
             include_path: self.include_path.clone(),
             hash: self.hash.clone(),
             dependencies: Vec::new(),
+            requirements: self.requirements.clone(),
+            features: self.features.clone(),
+            license: self.license.clone(),
             properties: Default::default(),
         }
     }
@@ -91,3 +243,20 @@ impl DependencyTreeNode {
         aggregator
     }
 }
+
+#[test]
+fn test_parse_git_url() {
+    assert_eq!(
+        parse_git_url("git@github.com:lf-lang/lingo.git")
+            .unwrap()
+            .as_str(),
+        "ssh://git@github.com/lf-lang/lingo.git"
+    );
+    assert_eq!(
+        parse_git_url("https://github.com/lf-lang/lingo.git")
+            .unwrap()
+            .as_str(),
+        "https://github.com/lf-lang/lingo.git"
+    );
+    assert!(parse_git_url("no-url").is_err());
+}
@@ -13,6 +13,16 @@ lazy_static! {  // FIXME: ad hoc parsing using regexes should be forbidden
 lazy_static! {
     static ref MAIN_REACTOR_RE: Regex = Regex::new(r"\bmain\s+reactor\s+(\w+)\s*[{(]").unwrap();
 }
+lazy_static! {
+    static ref LIBRARY_IMPORT_RE: Regex =
+        Regex::new(r"\bimport\b[^;<>\x22]*?\bfrom\s*<([^/>\s]+)/([^>]*)>").unwrap();
+}
+lazy_static! {
+    /// strings, code blocks and comments of an LF file, strings are matched so that comment
+    /// markers inside of them are ignored
+    static ref NON_LF_CODE_RE: Regex =
+        Regex::new(r#"(?s)"(?:[^"\\\n]|\\.)*"|\{=.*?=\}|/\*.*?\*/|(?://|#)[^\n]*"#).unwrap();
+}
 
 const DEFAULT_TARGET: TargetLanguage = TargetLanguage::C;
 
@@ -65,3 +75,84 @@ pub fn find_main_reactors(path: &Path) -> io::Result<Vec<MainReactorSpec>> {
     acc_main_reactors(&mut path.to_path_buf(), &mut main_reactors)?;
     Ok(main_reactors)
 }
+
+/// Import of a reactor from a library, like `import Client from <mqtt/Client.lf>`
+#[derive(Debug, PartialEq)]
+pub struct LibraryImport {
+    /// name of the package the reactor is imported from
+    pub library: String,
+    /// imported file relative to the directory with the LF files of the library
+    pub file: PathBuf,
+    /// file that contains the import
+    pub path: PathBuf,
+}
+
+/// removes comments and target code, which may contain text that looks like an import
+fn strip_comments(content: &str) -> String {
+    NON_LF_CODE_RE
+        .replace_all(content, |captures: &regex::Captures| {
+            let text = &captures[0];
+            if text.starts_with('"') {
+                text.to_string()
+            } else {
+                " ".to_string()
+            }
+        })
+        .into_owned()
+}
+
+/// Searches for imports from libraries in the LF files of the directory and descendants.
+pub fn find_library_imports(path: &Path) -> io::Result<Vec<LibraryImport>> {
+    let mut imports = Vec::new();
+    for entry in (std::fs::read_dir(path)?).flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            imports.append(&mut find_library_imports(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "lf") {
+            let content = strip_comments(&std::fs::read_to_string(&path)?);
+            for captures in LIBRARY_IMPORT_RE.captures_iter(&content) {
+                imports.push(LibraryImport {
+                    library: captures[1].to_string(),
+                    file: PathBuf::from(&captures[2]),
+                    path: path.clone(),
+                });
+            }
+        }
+    }
+    Ok(imports)
+}
+
+#[test]
+fn test_find_library_imports() {
+    let src = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(src.path().join("sensors")).unwrap();
+    std::fs::write(
+        src.path().join("Main.lf"),
+        "target C\nimport Client, Broker as B\n  from <mqtt/Client.lf>\nimport Local from \"Local.lf\"\nmain reactor {}",
+    )
+    .unwrap();
+    std::fs::write(
+        src.path().join("sensors/Sensor.lf"),
+        "target C\nimport Filter from <dsp/filters/Filter.lf>\nreactor Sensor {}",
+    )
+    .unwrap();
+    std::fs::write(
+        src.path().join("Commented.lf"),
+        "target C\n// import A from <old/A.lf>\n# import B from <old/B.lf>\n/* import C\n from <old/C.lf> */\nreactor R {\n  reaction(startup) {= /* import D from <old/D.lf> */ =}\n  state url: string = \"http://example.com\"\n}",
+    )
+    .unwrap();
+
+    let mut imports: Vec<(String, PathBuf)> = find_library_imports(src.path())
+        .unwrap()
+        .into_iter()
+        .map(|import| (import.library, import.file))
+        .collect();
+    imports.sort();
+    assert_eq!(
+        imports,
+        [
+            ("dsp".to_string(), PathBuf::from("filters/Filter.lf")),
+            ("mqtt".to_string(), PathBuf::from("Client.lf")),
+        ]
+    );
+}
@@ -0,0 +1,259 @@
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use url::Url;
+use xz2::read::XzDecoder;
+
+use crate::util::errors::LingoError;
+
+/// Archive formats that can be used as package sources
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveFormat {
+    TarGz,
+    TarXz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// determines the archive format from the file extension of the url
+    pub fn from_url(url: &Url) -> anyhow::Result<ArchiveFormat> {
+        let path = url.path();
+        if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Ok(ArchiveFormat::TarGz)
+        } else if path.ends_with(".tar.xz") || path.ends_with(".txz") {
+            Ok(ArchiveFormat::TarXz)
+        } else if path.ends_with(".zip") {
+            Ok(ArchiveFormat::Zip)
+        } else {
+            Err(LingoError::UnsupportedArchive(url.to_string()).into())
+        }
+    }
+}
+
+/// Checks that an archive member stays inside the extraction directory and removes the
+/// `strip_prefix` from it. Returns `None` for members that live outside the prefix.
+fn sanitize_member(member: &Path, strip_prefix: Option<&Path>) -> anyhow::Result<Option<PathBuf>> {
+    let mut relative = PathBuf::new();
+    for component in member.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(LingoError::UnsafeArchiveMember(member.display().to_string()).into());
+            }
+        }
+    }
+
+    match strip_prefix {
+        Some(prefix) => Ok(relative
+            .strip_prefix(prefix)
+            .ok()
+            .filter(|stripped| !stripped.as_os_str().is_empty())
+            .map(Path::to_path_buf)),
+        None if relative.as_os_str().is_empty() => Ok(None),
+        None => Ok(Some(relative)),
+    }
+}
+
+/// Checks that the target of a link inside the archive doesn't point outside the package.
+fn check_link_target(member: &Path, target: &Path) -> anyhow::Result<()> {
+    let mut depth = member.components().count() as isize - 1;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => depth -= 1,
+            Component::RootDir | Component::Prefix(_) => depth = -1,
+        }
+        if depth < 0 {
+            return Err(LingoError::UnsafeArchiveMember(format!(
+                "{} -> {}",
+                member.display(),
+                target.display()
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Checks that neither the member nor a directory on its way from the destination is a symlink.
+/// Links extracted before could otherwise redirect the member outside of the destination.
+fn check_no_symlinks(destination: &Path, relative: &Path) -> anyhow::Result<()> {
+    let mut path = destination.to_path_buf();
+    for component in relative.components() {
+        path.push(component);
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(LingoError::UnsafeArchiveMember(relative.display().to_string()).into());
+            }
+            Ok(_) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => break,
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(())
+}
+
+fn extract_tar<R: Read>(
+    reader: R,
+    destination: &Path,
+    strip_prefix: Option<&Path>,
+) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let member = entry.path()?.to_path_buf();
+        let Some(relative) = sanitize_member(&member, strip_prefix)? else {
+            continue;
+        };
+
+        check_no_symlinks(destination, &relative)?;
+        let target_path = destination.join(&relative);
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let link_name = entry.link_name()?.map(|link| link.to_path_buf());
+        match link_name {
+            // hard links name another member of the archive, which is copied instead
+            Some(link) if entry.header().entry_type().is_hard_link() => {
+                let source = sanitize_member(&link, strip_prefix)?.ok_or_else(|| {
+                    LingoError::UnsafeArchiveMember(format!(
+                        "{} -> {}",
+                        member.display(),
+                        link.display()
+                    ))
+                })?;
+                check_no_symlinks(destination, &source)?;
+                fs::copy(destination.join(source), &target_path)?;
+            }
+            Some(link) => {
+                check_link_target(&relative, &link)?;
+                entry.unpack(&target_path)?;
+            }
+            None => {
+                entry.unpack(&target_path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn extract_zip(
+    archive: &[u8],
+    destination: &Path,
+    strip_prefix: Option<&Path>,
+) -> anyhow::Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(archive))?;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let Some(relative) = sanitize_member(Path::new(file.name()), strip_prefix)? else {
+            continue;
+        };
+
+        check_no_symlinks(destination, &relative)?;
+        let target_path = destination.join(&relative);
+        if file.is_dir() {
+            fs::create_dir_all(&target_path)?;
+            continue;
+        }
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut output = fs::File::create(&target_path)?;
+        std::io::copy(&mut file, &mut output)?;
+
+        #[cfg(unix)]
+        if let Some(mode) = file.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&target_path, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    Ok(())
+}
+
+/// Extracts the archive into the destination folder. Members containing `..` or absolute
+/// paths are rejected. If a `strip_prefix` is given only the contents of this directory
+/// inside the archive are extracted.
+pub fn extract(
+    archive: &[u8],
+    format: ArchiveFormat,
+    destination: &Path,
+    strip_prefix: Option<&Path>,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(destination)?;
+    match format {
+        ArchiveFormat::TarGz => extract_tar(GzDecoder::new(archive), destination, strip_prefix),
+        ArchiveFormat::TarXz => extract_tar(XzDecoder::new(archive), destination, strip_prefix),
+        ArchiveFormat::Zip => extract_zip(archive, destination, strip_prefix),
+    }
+}
+
+#[test]
+fn test_sanitize_member() {
+    assert!(sanitize_member(Path::new("../evil"), None).is_err());
+    assert!(sanitize_member(Path::new("/etc/passwd"), None).is_err());
+    assert_eq!(
+        sanitize_member(
+            Path::new("pkg-1.0/src/lib/Foo.lf"),
+            Some(Path::new("pkg-1.0"))
+        )
+        .unwrap(),
+        Some(PathBuf::from("src/lib/Foo.lf"))
+    );
+    assert_eq!(
+        sanitize_member(Path::new("other/Foo.lf"), Some(Path::new("pkg-1.0"))).unwrap(),
+        None
+    );
+    assert!(check_link_target(Path::new("a/link"), Path::new("../../outside")).is_err());
+    assert!(check_link_target(Path::new("a/link"), Path::new("../inside")).is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_extract_chained_symlinks() {
+    let package = tempfile::tempdir().unwrap();
+    let tarball = |entries: &[(&str, Option<&str>, tar::EntryType)]| {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, link, kind) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(*kind);
+            header.set_mode(0o755);
+            header.set_size(if link.is_some() { 0 } else { 4 });
+            if let Some(link) = link {
+                header.set_link_name(link).unwrap();
+            }
+            let data: &[u8] = if link.is_some() { b"" } else { b"evil" };
+            builder.append_data(&mut header, path, data).unwrap();
+        }
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &builder.into_inner().unwrap()).unwrap();
+        encoder.finish().unwrap()
+    };
+
+    // every link stays inside on its own, but a/b/c resolves to the parent of the destination
+    let chained = tarball(&[
+        ("a/b", Some(".."), tar::EntryType::Symlink),
+        ("a/b/c", Some(".."), tar::EntryType::Symlink),
+        ("a/b/c/evil", None, tar::EntryType::Regular),
+    ]);
+    let destination = package.path().join("chained");
+    let error = extract(&chained, ArchiveFormat::TarGz, &destination, None).unwrap_err();
+    assert!(error.to_string().contains("a/b/c"));
+    assert!(!package.path().join("evil").exists());
+
+    // hard links are copied, which must not follow a symlink either
+    let hard_link = tarball(&[
+        ("a/up", Some(".."), tar::EntryType::Symlink),
+        ("secret", None, tar::EntryType::Regular),
+        ("stolen", Some("a/up/secret"), tar::EntryType::Link),
+    ]);
+    let destination = package.path().join("hard-link");
+    let error = extract(&hard_link, ArchiveFormat::TarGz, &destination, None).unwrap_err();
+    assert!(error.to_string().contains("a/up/secret"));
+    assert!(!destination.join("stolen").exists());
+}
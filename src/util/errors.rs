@@ -14,10 +14,30 @@ pub enum LingoError {
     CommandFailed(Command, ExitStatus),
     UnknownAppNames(Vec<String>),
     InvalidProjectLocation(PathBuf),
-    UseWestBuildToBuildApp,
     InvalidMainReactor,
     NoLibraryInLingoToml(String),
     LingoVersionMismatch(String),
+    LockFileOutdated(String),
+    OfflineFetch(String),
+    UnsupportedArchive(String),
+    UnsafeArchiveMember(String),
+    ArchiveChecksumMismatch(String, String, String),
+    NoRegistryConfigured(String),
+    PackageNotInRegistry(String, String),
+    NoMatchingVersion(String, String),
+    ChecksumMismatch(String, String, String),
+    LockVerificationFailed(Vec<String>),
+    UnknownFeature(String, String),
+    InvalidLicenseExpression(String, String),
+    LicensePolicyViolation(Vec<String>),
+    InvalidPackageJson(PathBuf),
+    UndeclaredImport(String, PathBuf),
+    MissingImport(String, PathBuf, PathBuf),
+    MissingBinary(PathBuf),
+    MissingRuntime(String),
+    InvalidRuntime(String, PathBuf, String),
+    UnsupportedPlatform(String, String),
+    MissingBoard(String),
 }
 
 impl Display for LingoError {
@@ -35,9 +55,6 @@ impl Display for LingoError {
             LingoError::InvalidProjectLocation(path) => {
                 write!(f, "Cannot initialize repository in {}", path.display())
             }
-            LingoError::UseWestBuildToBuildApp => {
-                write!(f, "Use `west lf-build` to build and run Zephyr programs.")
-            }
             LingoError::InvalidMainReactor => {
                 write!(
                     f,
@@ -56,6 +73,123 @@ impl Display for LingoError {
                     "Version specified in Lingo.toml doesn't match the version in the location {message}"
                 )
             }
+            LingoError::LockFileOutdated(reason) => {
+                write!(
+                    f,
+                    "Lingo.lock needs to be updated but --locked was specified: {reason}"
+                )
+            }
+            LingoError::OfflineFetch(name) => {
+                write!(
+                    f,
+                    "Dependency {name} has not been fetched yet and cannot be fetched in offline mode"
+                )
+            }
+            LingoError::UnsupportedArchive(url) => {
+                write!(
+                    f,
+                    "Cannot determine archive format of {url}, expected .tar.gz, .tar.xz or .zip"
+                )
+            }
+            LingoError::UnsafeArchiveMember(member) => {
+                write!(
+                    f,
+                    "Archive member {member} would be extracted outside of the package directory"
+                )
+            }
+            LingoError::ArchiveChecksumMismatch(url, expected, actual) => {
+                write!(
+                    f,
+                    "sha256 of {url} doesn't match, expected {expected} but got {actual}"
+                )
+            }
+            LingoError::NoRegistryConfigured(name) => {
+                write!(
+                    f,
+                    "Dependency {name} has no git, tarball or path source and no [registry] is configured in Lingo.toml"
+                )
+            }
+            LingoError::PackageNotInRegistry(name, index) => {
+                write!(f, "Package {name} cannot be found in the registry {index}")
+            }
+            LingoError::NoMatchingVersion(name, requirement) => {
+                write!(
+                    f,
+                    "No version of {name} satisfies the requirement {requirement}"
+                )
+            }
+            LingoError::ChecksumMismatch(name, expected, actual) => {
+                write!(
+                    f,
+                    "Checksum of {name} doesn't match Lingo.lock, expected {expected} but got {actual}"
+                )
+            }
+            LingoError::LockVerificationFailed(names) => {
+                write!(
+                    f,
+                    "Locked packages are missing or modified: {} (run `lingo verify --repair` to fetch them again)",
+                    names.join(", ")
+                )
+            }
+            LingoError::UnknownFeature(package, feature) => {
+                write!(
+                    f,
+                    "Package {package} has no feature or optional dependency named {feature}"
+                )
+            }
+            LingoError::InvalidLicenseExpression(expression, reason) => {
+                write!(f, "Invalid SPDX license expression {expression}: {reason}")
+            }
+            LingoError::LicensePolicyViolation(violations) => {
+                write!(
+                    f,
+                    "Dependencies violate the allowed-licenses policy: {}",
+                    violations.join("; ")
+                )
+            }
+            LingoError::InvalidPackageJson(path) => {
+                write!(f, "{} doesn't contain a JSON object", path.display())
+            }
+            LingoError::UndeclaredImport(library, path) => {
+                write!(
+                    f,
+                    "{} imports from the library {library}, which is not a dependency in the Lingo.toml",
+                    path.display()
+                )
+            }
+            LingoError::MissingImport(library, file, path) => {
+                write!(
+                    f,
+                    "{} imports {} from the library {library}, which doesn't contain it",
+                    path.display(),
+                    file.display()
+                )
+            }
+            LingoError::MissingBinary(path) => {
+                write!(f, "The build didn't produce the binary {}", path.display())
+            }
+            LingoError::MissingRuntime(variable) => {
+                write!(
+                    f,
+                    "Set {variable} to the directory of the runtime to build apps of this target"
+                )
+            }
+            LingoError::InvalidRuntime(variable, path, marker) => {
+                write!(
+                    f,
+                    "{variable} is set to {}, which doesn't contain the {marker} of the runtime",
+                    path.display()
+                )
+            }
+            LingoError::UnsupportedPlatform(platform, target) => {
+                write!(
+                    f,
+                    "The platform {platform} is not supported for {target} apps"
+                )
+            }
+            LingoError::MissingBoard(app) => {
+                write!(f, "The app {app} needs a `board` in the Lingo.toml")
+            }
         }
     }
 }
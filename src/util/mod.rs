@@ -1,10 +1,11 @@
 pub mod analyzer;
+pub mod archive;
 mod command_line;
 pub mod errors;
 pub mod sha1dir;
 
 pub use command_line::*;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::{fs, io};
 
 use crate::WhichCapability;
@@ -26,6 +27,24 @@ pub fn find_toml(input_path: &Path) -> Option<PathBuf> {
     None
 }
 
+/// expresses `path` relative to `base`, both paths are expected to be absolute
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    // paths on different drives can't be expressed relative to each other
+    if common == 0 {
+        return path.iter().collect();
+    }
+
+    let mut relative: PathBuf = base[common..].iter().map(|_| "..").collect();
+    relative.extend(&path[common..]);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
 /// Copy files from source to destination recursively.
 // Copied from https://nick.groenen.me/notes/recursively-copy-files-in-rust/
 pub fn copy_recursively(source: impl AsRef<Path>, destination: impl AsRef<Path>) -> io::Result<()> {
@@ -80,3 +99,17 @@ pub fn find_lfc_exec(
         "LFC executable not found",
     ))
 }
+
+#[test]
+fn test_relative_path() {
+    let base = Path::new("/home/user/app");
+    assert_eq!(
+        relative_path(Path::new("/home/user/libs/liba"), base),
+        PathBuf::from("../libs/liba")
+    );
+    assert_eq!(
+        relative_path(Path::new("/home/user/app/vendor/libb"), base),
+        PathBuf::from("vendor/libb")
+    );
+    assert_eq!(relative_path(base, base), PathBuf::from("."));
+}
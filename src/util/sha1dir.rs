@@ -12,13 +12,14 @@
     clippy::unseparated_literal_suffix
 )]
 
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use parking_lot::Mutex;
 use rayon::{Scope, ThreadPoolBuilder};
 use sha1::{Digest, Sha1};
 use std::error::Error;
 use std::fmt::{self, Display};
-use std::fs::{self, File, Metadata};
-use std::io::{self, Read, Write};
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::process;
 use std::sync::Once;
@@ -80,78 +81,123 @@ impl Checksum {
     }
 }
 
-fn get_file_as_byte_vec(filename: &Path) -> Vec<u8> {
-    let mut f = File::open(filename).expect("no file found");
-    let metadata = fs::metadata(filename).expect("unable to read metadata");
-    let mut buffer = vec![0; metadata.len() as usize];
-    f.read_exact(&mut buffer).expect("buffer overflow");
+/// Metadata directories of version control systems, they differ between checkouts of the
+/// same revision and are never part of the checksum.
+const VCS_DIRECTORIES: [&str; 4] = [".git", ".hg", ".svn", ".jj"];
 
-    buffer
+/// File inside the hashed directory listing paths that are excluded from the checksum, the
+/// patterns use the gitignore syntax.
+pub const IGNORE_FILE: &str = ".lingoignore";
+
+struct Walk<'a> {
+    base: &'a Path,
+    checksum: Checksum,
+    ignore: Gitignore,
+    ignore_unknown_filetypes: bool,
+    /// first error of the walk, the checksum is incomplete if there is one
+    error: Mutex<Option<anyhow::Error>>,
+}
+
+impl Walk<'_> {
+    fn fail<E: Display>(&self, path: &Path, error: E) {
+        self.error
+            .lock()
+            .get_or_insert_with(|| anyhow::anyhow!("{}: {}", path.display(), error));
+    }
+}
+
+/// Computes the checksum of the directory. Paths are hashed relative to the directory, so the
+/// result neither depends on the current working directory nor on where the directory lives.
+pub fn checksum_dir(path: &Path, ignore_unknown_filetypes: bool) -> anyhow::Result<Checksum> {
+    checksum_dir_ignoring(path, ignore_unknown_filetypes, &[])
 }
 
-pub fn checksum_current_dir(label: &Path, ignore_unknown_filetypes: bool) -> Checksum {
-    let checksum = Checksum::new();
+/// Like [`checksum_dir`], but also leaves out the paths matching the given gitignore patterns.
+pub fn checksum_dir_ignoring(
+    path: &Path,
+    ignore_unknown_filetypes: bool,
+    patterns: &[&str],
+) -> anyhow::Result<Checksum> {
+    let ignore_file = path.join(IGNORE_FILE);
+    let mut builder = GitignoreBuilder::new(path);
+    if ignore_file.exists() {
+        if let Some(error) = builder.add(&ignore_file) {
+            anyhow::bail!("{}: {}", ignore_file.display(), error);
+        }
+    }
+    for pattern in patterns {
+        builder.add_line(None, pattern)?;
+    }
+    let ignore = builder.build()?;
+
+    let walk = Walk {
+        base: path,
+        checksum: Checksum::new(),
+        ignore,
+        ignore_unknown_filetypes,
+        error: Mutex::new(None),
+    };
     rayon::scope(|scope| {
-        if let Err(error) = (|| -> Result<()> {
-            for child in Path::new(".").read_dir()? {
-                let child = child?;
-                scope.spawn({
-                    let checksum = &checksum;
-                    move |scope| {
-                        entry(
-                            scope,
-                            label,
-                            checksum,
-                            Path::new(&child.file_name()),
-                            ignore_unknown_filetypes,
-                        );
-                    }
-                });
-            }
-            Ok(())
-        })() {
-            die(label, error);
+        if let Err(error) = children(scope, &walk, Path::new("")) {
+            walk.fail(path, error);
         }
     });
-    checksum
+    match walk.error.into_inner() {
+        Some(error) => Err(error),
+        None => Ok(walk.checksum),
+    }
 }
 
-fn entry<'scope>(
-    scope: &Scope<'scope>,
-    base: &'scope Path,
-    checksum: &'scope Checksum,
-    path: &Path,
-    ignore_unknown_filetypes: bool,
-) {
-    let metadata = match path.symlink_metadata() {
+fn children<'scope>(scope: &Scope<'scope>, walk: &'scope Walk, path: &Path) -> Result<()> {
+    for child in walk.base.join(path).read_dir()? {
+        let child = child?;
+        let is_dir = child.file_type()?.is_dir();
+        let child_path = path.join(child.file_name());
+
+        if is_dir && VCS_DIRECTORIES.iter().any(|vcs| child.file_name() == *vcs) {
+            continue;
+        }
+        if walk.ignore.matched(&child_path, is_dir).is_ignore() {
+            continue;
+        }
+
+        scope.spawn(move |scope| entry(scope, walk, &child_path));
+    }
+
+    Ok(())
+}
+
+fn entry<'scope>(scope: &Scope<'scope>, walk: &'scope Walk, path: &Path) {
+    let full_path = walk.base.join(path);
+    let metadata = match full_path.symlink_metadata() {
         Ok(metadata) => metadata,
-        Err(error) => die(base.join(path), error),
+        Err(error) => return walk.fail(&full_path, error),
     };
 
     let file_type = metadata.file_type();
     let result = if file_type.is_file() {
-        file(checksum, path, metadata)
+        file(&walk.checksum, &full_path, path, metadata)
     } else if file_type.is_symlink() {
-        symlink(checksum, path)
+        symlink(&walk.checksum, &full_path, path)
     } else if file_type.is_dir() {
-        dir(scope, base, checksum, path, ignore_unknown_filetypes)
-    } else if ignore_unknown_filetypes {
+        dir(scope, walk, path)
+    } else if walk.ignore_unknown_filetypes {
         Ok(())
     } else {
-        die(base.join(path), "Unsupported file type");
+        Err("Unsupported file type".into())
     };
 
     if let Err(error) = result {
-        die(base.join(path), error);
+        walk.fail(&full_path, error);
     }
 }
 
-fn file(checksum: &Checksum, path: &Path, metadata: Metadata) -> Result<()> {
+fn file(checksum: &Checksum, full_path: &Path, path: &Path, metadata: Metadata) -> Result<()> {
     let mut sha = begin(path, b'f');
 
     // Enforced by memmap: "memory map must have a non-zero length"
     if metadata.len() > 0 {
-        sha.update(get_file_as_byte_vec(path));
+        sha.update(fs::read(full_path)?);
     }
 
     checksum.put(sha);
@@ -159,35 +205,30 @@ fn file(checksum: &Checksum, path: &Path, metadata: Metadata) -> Result<()> {
     Ok(())
 }
 
-fn symlink(checksum: &Checksum, path: &Path) -> Result<()> {
+fn symlink(checksum: &Checksum, full_path: &Path, path: &Path) -> Result<()> {
     let mut sha = begin(path, b'l');
-    sha.update(path.read_link()?.as_os_str().as_encoded_bytes());
+    sha.update(full_path.read_link()?.as_os_str().as_encoded_bytes());
     checksum.put(sha);
 
     Ok(())
 }
 
-fn dir<'scope>(
-    scope: &Scope<'scope>,
-    base: &'scope Path,
-    checksum: &'scope Checksum,
-    path: &Path,
-    ignore_unknown_filetypes: bool,
-) -> Result<()> {
+fn dir<'scope>(scope: &Scope<'scope>, walk: &'scope Walk, path: &Path) -> Result<()> {
     let sha = begin(path, b'd');
-    checksum.put(sha);
-
-    for child in path.read_dir()? {
-        let child = child?.path();
-        scope.spawn(move |scope| entry(scope, base, checksum, &child, ignore_unknown_filetypes));
-    }
+    walk.checksum.put(sha);
 
-    Ok(())
+    children(scope, walk, path)
 }
 
 fn begin(path: &Path, kind: u8) -> Sha1 {
     let mut sha = Sha1::new();
-    let path_bytes = path.as_os_str().as_encoded_bytes();
+    // the same separator on every platform
+    let path_string = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let path_bytes = path_string.as_bytes();
     sha.update([kind]);
     sha.update((path_bytes.len() as u32).to_le_bytes());
     sha.update(path_bytes);
@@ -196,5 +237,62 @@ fn begin(path: &Path, kind: u8) -> Sha1 {
 
 #[test]
 fn test_cli() {
-    <Opt as clap::CommandFactory>::command().debug_assert();
+    use clap::Args;
+    crate::args::HashArgs::augment_args(clap::Command::new("hash")).debug_assert();
+}
+
+#[test]
+fn test_checksum_dir() {
+    let write = |dir: &Path, file: &str, content: &str| {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    };
+
+    let first = tempfile::tempdir().unwrap();
+    let second = tempfile::tempdir().unwrap();
+    for dir in [first.path(), second.path()] {
+        write(dir, "Lingo.toml", "[package]");
+        write(dir, "src/lib/Lib.lf", "reactor Lib {}");
+        write(dir, ".lingoignore", "*.log\n/build/\n");
+    }
+
+    // metadata and ignored files don't change the checksum
+    write(first.path(), ".git/HEAD", "ref: refs/heads/main");
+    write(first.path(), "src/debug.log", "output");
+    write(first.path(), "build/out", "binary");
+    assert_eq!(
+        checksum_dir(first.path(), false).unwrap().to_string(),
+        checksum_dir(second.path(), false).unwrap().to_string()
+    );
+
+    write(first.path(), "src/lib/Lib.lf", "reactor Changed {}");
+    assert_ne!(
+        checksum_dir(first.path(), false).unwrap().to_string(),
+        checksum_dir(second.path(), false).unwrap().to_string()
+    );
+}
+
+#[test]
+fn test_checksum_dir_ignoring() {
+    let first = tempfile::tempdir().unwrap();
+    let second = tempfile::tempdir().unwrap();
+    for dir in [first.path(), second.path()] {
+        fs::write(dir.join("main.py"), "print()").unwrap();
+    }
+    fs::create_dir_all(first.path().join("build/lib")).unwrap();
+    fs::write(first.path().join("build/lib/ext.so"), "binary").unwrap();
+
+    let checksum = |dir: &Path| {
+        checksum_dir_ignoring(dir, false, &["/build/"])
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(checksum(first.path()), checksum(second.path()));
+
+    let missing = first.path().join("missing");
+    let error = checksum_dir(&missing, false).err().unwrap();
+    assert!(error
+        .to_string()
+        .starts_with(&missing.display().to_string()));
 }